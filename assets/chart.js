@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+// Small offline charting layer for the loggregate report. Draws the series
+// given in LOGGREGATE_DATA as lines in an svg, supports zooming with the
+// mouse wheel, panning by dragging, hover tooltips and toggling of levels
+// through the legend. Double click resets the zoom.
+
+(function () {
+    "use strict";
+
+    const SVG_NS = "http://www.w3.org/2000/svg";
+    const WIDTH = 1000;
+    const HEIGHT = 420;
+    const PAD = { top: 20, right: 20, bottom: 70, left: 60 };
+    const PLOT_W = WIDTH - PAD.left - PAD.right;
+    const PLOT_H = HEIGHT - PAD.top - PAD.bottom;
+    const MAX_X_LABELS = 10;
+
+    function svgEl(name, attrs, parent) {
+        const el = document.createElementNS(SVG_NS, name);
+        for (const key in attrs) {
+            el.setAttribute(key, attrs[key]);
+        }
+        if (parent) {
+            parent.appendChild(el);
+        }
+        return el;
+    }
+
+    function niceStep(max, ticks) {
+        const raw = Math.max(max, 1) / ticks;
+        const mag = Math.pow(10, Math.floor(Math.log10(raw)));
+        const norm = raw / mag;
+        const nice = norm <= 1 ? 1 : norm <= 2 ? 2 : norm <= 5 ? 5 : 10;
+        return Math.max(1, nice * mag);
+    }
+
+    function drawChart(container, data) {
+        const numBuckets = data.labels.length;
+        const hidden = new Set();
+        let view = { from: 0, to: Math.max(numBuckets - 1, 1) };
+
+        const svg = svgEl("svg", {
+            viewBox: "0 0 " + WIDTH + " " + HEIGHT,
+            class: "chart-svg",
+        }, container);
+        const clip = svgEl("clipPath", { id: "chart-clip" }, svgEl("defs", {}, svg));
+        svgEl("rect", { x: PAD.left, y: PAD.top, width: PLOT_W, height: PLOT_H }, clip);
+        const axes = svgEl("g", {}, svg);
+        const lines = svgEl("g", { "clip-path": "url(#chart-clip)" }, svg);
+        const guide = svgEl("line", {
+            y1: PAD.top, y2: PAD.top + PLOT_H, stroke: "#888", "stroke-dasharray": "4 4",
+            visibility: "hidden",
+        }, svg);
+        const overlay = svgEl("rect", {
+            x: PAD.left, y: PAD.top, width: PLOT_W, height: PLOT_H,
+            fill: "transparent", class: "chart-overlay",
+        }, svg);
+
+        const tooltip = document.createElement("div");
+        tooltip.className = "chart-tooltip";
+        container.appendChild(tooltip);
+
+        const legend = document.createElement("div");
+        legend.className = "chart-legend";
+        container.appendChild(legend);
+
+        function xPos(idx) {
+            return PAD.left + ((idx - view.from) / (view.to - view.from)) * PLOT_W;
+        }
+
+        function visibleMax() {
+            let max = 0;
+            const from = Math.max(0, Math.floor(view.from));
+            const to = Math.min(numBuckets - 1, Math.ceil(view.to));
+            for (const series of data.series) {
+                if (hidden.has(series.name)) {
+                    continue;
+                }
+                for (let i = from; i <= to; i++) {
+                    max = Math.max(max, series.values[i]);
+                }
+            }
+            return max;
+        }
+
+        function render() {
+            axes.replaceChildren();
+            lines.replaceChildren();
+
+            const step = niceStep(visibleMax(), 5);
+            const yMax = Math.max(step, Math.ceil(visibleMax() / step) * step);
+            const yPos = (v) => PAD.top + PLOT_H - (v / yMax) * PLOT_H;
+
+            for (let v = 0; v <= yMax; v += step) {
+                svgEl("line", {
+                    x1: PAD.left, x2: PAD.left + PLOT_W, y1: yPos(v), y2: yPos(v),
+                    stroke: "#e0e0e0",
+                }, axes);
+                svgEl("text", {
+                    x: PAD.left - 8, y: yPos(v) + 4, "text-anchor": "end", class: "chart-label",
+                }, axes).textContent = v;
+            }
+
+            const from = Math.max(0, Math.ceil(view.from));
+            const to = Math.min(numBuckets - 1, Math.floor(view.to));
+            const labelEvery = Math.max(1, Math.ceil((to - from + 1) / MAX_X_LABELS));
+            for (let i = from; i <= to; i += labelEvery) {
+                const x = xPos(i);
+                svgEl("line", {
+                    x1: x, x2: x, y1: PAD.top + PLOT_H, y2: PAD.top + PLOT_H + 5, stroke: "#000",
+                }, axes);
+                svgEl("text", {
+                    x: x, y: PAD.top + PLOT_H + 18, "text-anchor": "end", class: "chart-label",
+                    transform: "rotate(-30 " + x + " " + (PAD.top + PLOT_H + 18) + ")",
+                }, axes).textContent = data.labels[i];
+            }
+            svgEl("rect", {
+                x: PAD.left, y: PAD.top, width: PLOT_W, height: PLOT_H,
+                fill: "none", stroke: "#000",
+            }, axes);
+
+            const clipFrom = Math.max(0, Math.floor(view.from));
+            const clipTo = Math.min(numBuckets - 1, Math.ceil(view.to));
+            for (const series of data.series) {
+                if (hidden.has(series.name)) {
+                    continue;
+                }
+                const points = [];
+                for (let i = clipFrom; i <= clipTo; i++) {
+                    points.push(xPos(i).toFixed(1) + "," + yPos(series.values[i]).toFixed(1));
+                }
+                svgEl("polyline", {
+                    points: points.join(" "), fill: "none", stroke: series.color,
+                    "stroke-width": 2,
+                }, lines);
+            }
+        }
+
+        function renderLegend() {
+            legend.replaceChildren();
+            for (const series of data.series) {
+                const item = document.createElement("span");
+                item.className = "chart-legend-item" + (hidden.has(series.name) ? " off" : "");
+                const swatch = document.createElement("i");
+                swatch.style.background = series.color;
+                item.appendChild(swatch);
+                item.appendChild(document.createTextNode(series.name));
+                item.addEventListener("click", function () {
+                    if (hidden.has(series.name)) {
+                        hidden.delete(series.name);
+                    } else {
+                        hidden.add(series.name);
+                    }
+                    renderLegend();
+                    render();
+                });
+                legend.appendChild(item);
+            }
+        }
+
+        function bucketAt(clientX) {
+            const box = svg.getBoundingClientRect();
+            const x = ((clientX - box.left) / box.width) * WIDTH;
+            return view.from + ((x - PAD.left) / PLOT_W) * (view.to - view.from);
+        }
+
+        function clampView(from, to) {
+            const span = Math.min(Math.max(to - from, 1), Math.max(numBuckets - 1, 1));
+            from = Math.max(0, Math.min(from, numBuckets - 1 - span));
+            view = { from: from, to: from + span };
+        }
+
+        overlay.addEventListener("wheel", function (event) {
+            event.preventDefault();
+            const center = bucketAt(event.clientX);
+            const factor = event.deltaY < 0 ? 0.8 : 1.25;
+            clampView(
+                center - (center - view.from) * factor,
+                center + (view.to - center) * factor
+            );
+            render();
+        }, { passive: false });
+
+        let dragStart = null;
+        overlay.addEventListener("mousedown", function (event) {
+            dragStart = bucketAt(event.clientX);
+        });
+        window.addEventListener("mouseup", function () {
+            dragStart = null;
+        });
+        overlay.addEventListener("dblclick", function () {
+            view = { from: 0, to: Math.max(numBuckets - 1, 1) };
+            render();
+        });
+
+        overlay.addEventListener("mousemove", function (event) {
+            if (dragStart !== null) {
+                // Keep the bucket grabbed at mousedown under the cursor
+                const shift = dragStart - bucketAt(event.clientX);
+                clampView(view.from + shift, view.to + shift);
+                render();
+            }
+
+            const idx = Math.round(bucketAt(event.clientX));
+            if (idx < 0 || idx >= numBuckets) {
+                return;
+            }
+            const x = xPos(idx);
+            guide.setAttribute("x1", x);
+            guide.setAttribute("x2", x);
+            guide.setAttribute("visibility", "visible");
+
+            const rows = data.series
+                .filter((series) => !hidden.has(series.name))
+                .map((series) => "<tr><td><i style=\"background:" + series.color + "\"></i>"
+                    + series.name + "</td><td>" + series.values[idx] + "</td></tr>");
+            tooltip.innerHTML = "<b>" + data.labels[idx] + "</b><table>" + rows.join("") + "</table>";
+            tooltip.style.display = "block";
+            const box = container.getBoundingClientRect();
+            const left = event.clientX - box.left + 16;
+            tooltip.style.left = Math.min(left, box.width - tooltip.offsetWidth) + "px";
+            tooltip.style.top = (event.clientY - box.top + 16) + "px";
+        });
+        overlay.addEventListener("mouseleave", function () {
+            guide.setAttribute("visibility", "hidden");
+            tooltip.style.display = "none";
+        });
+
+        renderLegend();
+        render();
+    }
+
+    const container = document.getElementById("trend-chart");
+    if (container && typeof LOGGREGATE_DATA !== "undefined") {
+        drawChart(container, LOGGREGATE_DATA);
+    }
+})();
@@ -0,0 +1,193 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Analyses of a log file from reading it to the aggregated counts, done the
+//! way loggregate did before the lines were parsed as bytes against the way
+//! it does now. Run with `cargo bench`.
+//!
+//! The baseline reads the file into a string and copies every line, like the
+//! input did, and parses the lines with the regex parser as it was, which
+//! copies every message and counts its pattern. It then goes through the
+//! same `analyse_logs` as the others, so the splitting into spans, the
+//! aggregation and the metrics are the new ones in every case and only the
+//! reading and parsing differ.
+
+#![allow(clippy::needless_return)]
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::thread::available_parallelism;
+
+use chrono::NaiveDateTime;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use loggregate::analyse::dtfmt::dt_fmt_to_regex;
+use loggregate::analyse::gaps::GapOptions;
+use loggregate::analyse::loglevel::{loglevel_regex_pattern, to_loglevel, LogLevel};
+use loggregate::analyse::parser::{LineParseError, LogParser, LogRecord, RegexParser};
+use loggregate::analyse::{analyse_logs, LogSource, LogsAnalysis};
+use loggregate::input::LogBytes;
+use loggregate::{GlobSource, InputSource, LineFilter};
+use regex::Regex;
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const NO_OF_LINES: usize = 200_000;
+
+// The regex parser as it was before the lines were parsed as bytes, it
+// matches on `&str`, upper cases the loglevel to look it up and copies the
+// message out of the line
+struct StringParser {
+    dt_regex: Regex,
+    ll_regex: Regex,
+}
+
+impl LogParser for StringParser {
+    fn parse<'a>(&self, log: &'a str) -> Result<LogRecord<'a>, LineParseError> {
+        let datetime_match = match self.dt_regex.find(log) {
+            Some(datetime_match) => datetime_match,
+            _ => return Err(LineParseError::NoDatetimeMatch),
+        };
+
+        let datetime = NaiveDateTime::parse_from_str(datetime_match.as_str(), DATETIME_FORMAT)
+            .map_err(|_| LineParseError::DatetimeParseError)?;
+
+        let (loglevel, message_start) = match self.ll_regex.find(log) {
+            Some(ll_match) => (
+                to_loglevel(&ll_match.as_str().to_uppercase()),
+                ll_match.end().max(datetime_match.end()),
+            ),
+            _ => (LogLevel::Others, datetime_match.end()),
+        };
+        let message = log[message_start..]
+            .trim_start_matches(|c: char| c.is_whitespace() || "-:|".contains(c))
+            .to_owned();
+
+        return Ok(LogRecord {
+            datetime,
+            loglevel,
+            message: Cow::Owned(message),
+            fields: BTreeMap::new(),
+        });
+    }
+}
+
+fn write_sample_log() -> PathBuf {
+    let levels = ["INFO", "DEBUG", "WARN", "ERROR", "notice"];
+    let mut text = String::new();
+    for idx in 0..NO_OF_LINES {
+        let datetime = NaiveDateTime::parse_from_str("2024-03-01 00:00:00", DATETIME_FORMAT)
+            .unwrap()
+            + chrono::TimeDelta::seconds(idx as i64 / 3);
+        if idx % 1000 == 999 {
+            text.push_str("continuation line without a datetime\n");
+            continue;
+        }
+        text.push_str(&format!(
+            "{} [{}] worker-{} handled request {} from 10.0.{}.{} in {}ms\n",
+            datetime.format(DATETIME_FORMAT),
+            levels[idx % levels.len()],
+            idx % 8,
+            idx,
+            idx % 4,
+            idx % 250,
+            idx % 250
+        ));
+    }
+    let path = std::env::temp_dir().join("loggregate-bench.log");
+    write(&path, text).unwrap();
+    return path;
+}
+
+fn analyse(
+    log_sources: &[LogSource],
+    log_parser: &dyn LogParser,
+    message_patterns: bool,
+) -> LogsAnalysis {
+    let line_filter = LineFilter {
+        include: &[],
+        exclude: &[],
+    };
+    let gap_options = GapOptions {
+        min_gap: None,
+        silence_ratio: 0.1,
+    };
+    let jobs = available_parallelism().map_or(1, usize::from);
+    return analyse_logs(
+        log_sources,
+        None,
+        &line_filter,
+        log_parser,
+        gap_options,
+        &[],
+        message_patterns,
+        jobs,
+    );
+}
+
+fn read_glob(path: &str, mmap: bool) -> Vec<LogSource> {
+    let mut log_sources: Vec<LogSource> = Vec::new();
+    GlobSource::new(vec![path.to_owned()])
+        .mmap(mmap)
+        .read_logs(&mut log_sources)
+        .unwrap();
+    return log_sources;
+}
+
+fn bench_analysis(c: &mut Criterion) {
+    let path = write_sample_log();
+    let path_str = path.to_string_lossy().into_owned();
+    let file_size = std::fs::metadata(&path).unwrap().len();
+
+    let string_parser = StringParser {
+        dt_regex: dt_fmt_to_regex(DATETIME_FORMAT),
+        ll_regex: loglevel_regex_pattern(),
+    };
+    let regex_parser = RegexParser::new(DATETIME_FORMAT);
+
+    let mut group = c.benchmark_group("analyse_logs");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(file_size));
+
+    group.bench_function("baseline", |b| {
+        b.iter(|| {
+            let text = read_to_string(&path).unwrap();
+            let lines: Vec<String> = text.lines().map(str::to_owned).collect();
+            let log_sources = vec![LogSource {
+                name: path_str.clone(),
+                bytes: LogBytes::Owned(text.into_bytes()),
+                file_id: None,
+                modified: None,
+            }];
+            let analysis = analyse(&log_sources, &string_parser, true);
+            black_box((lines.len(), analysis.no_of_lines))
+        })
+    });
+
+    group.bench_function("bytes", |b| {
+        b.iter(|| {
+            let log_sources = read_glob(&path_str, false);
+            black_box(analyse(&log_sources, &regex_parser, true).no_of_lines)
+        })
+    });
+
+    // What the csv, parquet and text reports cost, they show no patterns
+    group.bench_function("bytes_without_patterns", |b| {
+        b.iter(|| {
+            let log_sources = read_glob(&path_str, false);
+            black_box(analyse(&log_sources, &regex_parser, false).no_of_lines)
+        })
+    });
+
+    group.bench_function("mapped_bytes", |b| {
+        b.iter(|| {
+            let log_sources = read_glob(&path_str, true);
+            black_box(analyse(&log_sources, &regex_parser, true).no_of_lines)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_analysis);
+criterion_main!(benches);
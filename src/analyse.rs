@@ -1,28 +1,71 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use chrono::NaiveDateTime;
-use dtfmt::dt_fmt_to_regex;
-use loglevel::loglevel_regex_pattern;
-use std::{collections::HashMap, collections::HashSet};
+use chrono::{NaiveDateTime, TimeDelta};
+use memchr::{memchr, memrchr};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 mod aggregate;
 pub mod calc;
 pub mod dtfmt;
+pub mod gaps;
 pub mod loglevel;
+pub mod metrics;
+pub mod parser;
+pub mod patterns;
+pub mod rules;
+pub mod threshold;
 
 use crate::analyse::aggregate::{
     aggregate_in_terms_of_days, aggregate_in_terms_of_hours, aggregate_in_terms_of_minutes,
-    aggregate_in_terms_of_months, aggregate_in_terms_of_years,
+    aggregate_in_terms_of_months, aggregate_in_terms_of_years, count_by_hour_and_weekday,
 };
+use crate::input::{byte_lines, LineFilter, LogBytes};
+use crate::parallel::map_chunks;
 
 use self::aggregate::aggregate_in_terms_of_seconds;
-use self::dtfmt::DateTimeCat;
-use self::loglevel::{to_loglevel, LogLevel};
+use self::dtfmt::{bucket_start, DateTimeCat};
+use self::gaps::{detect_gaps, GapAnalysis, GapOptions};
+use self::loglevel::{make_loglevel_count_vec_map, LogLevel};
+use self::metrics::{compute_metrics, LogMetrics};
+use self::parser::{LineParseError, LogParser, LogRecord};
+use self::patterns::{MessagePattern, PatternCounter, TOP_PATTERNS};
+use self::rules::{evaluate_rules, AlertRule, RuleFiring, RuleTarget};
 
 #[derive(Copy, Clone)]
 struct DateTimeLogLevelMap {
     datetime: NaiveDateTime,
     loglevel: LogLevel,
+    /// Index of the log source the line was read from
+    source: usize,
+}
+
+/// File or stream the logs were read from.
+pub struct LogSource {
+    pub name: String,
+    pub bytes: LogBytes,
+    /// Inode of the file, none for streams and on systems without inodes
+    pub file_id: Option<u64>,
+    /// Last write to the file in local time, none for streams
+    pub modified: Option<NaiveDateTime>,
+}
+
+impl LogSource {
+    /// Lines of the source without their line endings, they may not be valid
+    /// UTF-8.
+    pub fn lines(&self) -> impl Iterator<Item = &[u8]> {
+        return byte_lines(&self.bytes);
+    }
+}
+
+/// A line that could not be parsed and where it was read from.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RejectedLine {
+    pub source: String,
+    pub line_number: usize,
+    pub reason: LineParseError,
+    pub line: String,
 }
 
 pub struct LogsAggregate {
@@ -31,93 +74,774 @@ pub struct LogsAggregate {
     pub aggregates: HashMap<LogLevel, Vec<i32>>,
 }
 
+/// Number of logs for every hour of the day (columns) on every day of the
+/// week starting from Monday (rows).
+pub type HourWeekdayCounts = [[i32; 24]; 7];
+
+pub struct LogsAnalysis {
+    pub logs_aggregate: LogsAggregate,
+    pub parse_failures: ParseFailures,
+    pub hour_weekday_counts: HashMap<LogLevel, HourWeekdayCounts>,
+    pub metrics: LogMetrics,
+    pub gaps: GapAnalysis,
+    /// Firings of the alert rules, none when no rules were given
+    pub rule_firings: Option<Vec<RuleFiring>>,
+    /// Most frequent messages, most frequent first
+    pub message_patterns: Vec<MessagePattern>,
+    /// Datetimes of the first and the last log, none when no line was parsed
+    pub time_span: Option<(NaiveDateTime, NaiveDateTime)>,
+    /// First lines that could not be parsed, up to [`REJECTS_SAMPLE_SIZE`]
+    /// for every reason
+    pub rejected_lines: Vec<RejectedLine>,
+    /// Number of lines kept by the include and exclude filters
+    pub no_of_lines: i32,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct ParseFailures {
+    pub no_datetime_match: i32,
+    pub datetime_parse_error: i32,
+    pub malformed_record: i32,
+    /// Lines without a known loglevel, they are still analysed as `Others`
+    pub level_not_found: i32,
+}
+
+impl ParseFailures {
+    /// Number of lines that could not be parsed for any reason, the lines
+    /// without a known loglevel are not counted as they are analysed
+    pub fn total(&self) -> i32 {
+        return self.no_datetime_match + self.datetime_parse_error + self.malformed_record;
+    }
+
+    fn merge(&mut self, other: &ParseFailures) {
+        self.no_datetime_match += other.no_datetime_match;
+        self.datetime_parse_error += other.datetime_parse_error;
+        self.malformed_record += other.malformed_record;
+        self.level_not_found += other.level_not_found;
+    }
+}
+
+/// Number of the rejected lines kept for every reason a line can fail to parse
+pub const REJECTS_SAMPLE_SIZE: i32 = 1000;
+
 const MIN_SECONDS: i64 = 60;
 const HOUR_SECONDS: i64 = MIN_SECONDS * 60;
 const DAY_SECONDS: i64 = HOUR_SECONDS * 24;
 const MONTH_SECONDS: i64 = DAY_SECONDS * 31;
 const YEAR_SECONDS: i64 = MONTH_SECONDS * 12;
 
-pub fn analyse_logs(log_lines: &Vec<String>, datetime_format: &str) -> LogsAggregate {
-    let mut log_data: Vec<DateTimeLogLevelMap> = Vec::new();
-    let mut unq_datetimes: HashSet<NaiveDateTime> = HashSet::new();
-    let logs_aggregate: LogsAggregate;
-
-    let dt_regex = dt_fmt_to_regex(datetime_format);
-    let ll_regex = loglevel_regex_pattern();
-    for log in log_lines {
-        let datetime_str = match dt_regex.captures(&log) {
-            Some(caps) => caps.get(0).unwrap().as_str().to_owned(),
-            _ => {
-                eprintln!("Error finding date with given format in line: {}", log);
-                continue;
+/// Smallest part of a file parsed by a worker, smaller files are not split
+const MIN_SPAN_BYTES: usize = 1 << 20;
+/// Most frequent message patterns kept in the cache of a source, more than
+/// are shown so that the top ones stay right once the counts of several
+/// files and runs are added up
+const CACHED_PATTERNS: usize = TOP_PATTERNS * 10;
+
+// Lines of a log source parsed by one worker
+struct Span<'a> {
+    source: usize,
+    bytes: &'a [u8],
+    /// Last line of the source without a line ending yet, it is analysed but
+    /// left out of the cache of the source
+    tail: bool,
+}
+
+/// What the analysis of a log source found in its lines up to `offset`, so
+/// that a later analysis of the same source only parses the lines appended
+/// since. Its size does not grow with the number of lines: logs are counted
+/// by `unit`, which is never wider than the time buckets so that they still
+/// fall in the same buckets, and only the most frequent patterns and the
+/// first rejected lines are kept. The first and the last log of every period
+/// keep their datetime, a gap is longer than a period so it starts and ends
+/// with one of them.
+#[derive(Deserialize, Serialize)]
+pub struct SourceCache {
+    /// Bytes of the source parsed, up to the end of its last complete line
+    pub offset: usize,
+    lines_read: usize,
+    lines_kept: i32,
+    /// Width of the periods the logs and pattern matches are counted in, the
+    /// source is parsed again when the buckets or the gaps become narrower
+    /// than that
+    unit: DateTimeCat,
+    /// Number of logs of every loglevel starting every period
+    logs: Vec<(NaiveDateTime, LogLevel, i32)>,
+    /// Datetimes of the first and the last log of every period with logs, as
+    /// they were in the lines
+    log_spans: Vec<(NaiveDateTime, NaiveDateTime)>,
+    parse_failures: ParseFailures,
+    pattern_matches: Vec<Vec<(NaiveDateTime, i32)>>,
+    /// Up to [`CACHED_PATTERNS`] of the most frequent patterns
+    message_patterns: Vec<MessagePattern>,
+    /// Up to [`REJECTS_SAMPLE_SIZE`] lines of every reason
+    rejected_lines: Vec<RejectedLine>,
+}
+
+impl SourceCache {
+    fn new(chunk: &ParsedChunk, offset: usize, unit: DateTimeCat) -> SourceCache {
+        let mut log_counts: BTreeMap<(NaiveDateTime, LogLevel), i32> = BTreeMap::new();
+        let mut log_spans: BTreeMap<NaiveDateTime, (NaiveDateTime, NaiveDateTime)> =
+            BTreeMap::new();
+        for log in &chunk.log_data {
+            let period = bucket_start(log.datetime, unit);
+            *log_counts.entry((period, log.loglevel)).or_insert(0) += 1;
+            let (first, last) = log_spans
+                .entry(period)
+                .or_insert((log.datetime, log.datetime));
+            *first = log.datetime.min(*first);
+            *last = log.datetime.max(*last);
+        }
+        let logs: Vec<(NaiveDateTime, LogLevel, i32)> = log_counts
+            .into_iter()
+            .map(|((datetime, loglevel), count)| (datetime, loglevel, count))
+            .collect();
+
+        let pattern_matches = chunk
+            .pattern_matches
+            .iter()
+            .map(|datetimes| {
+                let mut match_counts: BTreeMap<NaiveDateTime, i32> = BTreeMap::new();
+                for datetime in datetimes {
+                    *match_counts
+                        .entry(bucket_start(*datetime, unit))
+                        .or_insert(0) += 1;
+                }
+                match_counts.into_iter().collect()
+            })
+            .collect();
+
+        return SourceCache {
+            offset,
+            lines_read: chunk.lines_read,
+            lines_kept: chunk.lines_kept,
+            unit,
+            logs,
+            log_spans: log_spans.into_values().collect(),
+            parse_failures: chunk.parse_failures.clone(),
+            pattern_matches,
+            message_patterns: chunk.pattern_counter.top(CACHED_PATTERNS),
+            rejected_lines: chunk.rejected_lines.clone(),
+        };
+    }
+
+    fn time_span(&self) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        return Some((self.log_spans.first()?.0, self.log_spans.last()?.1));
+    }
+
+    // The cached logs as if the lines were parsed again from source `source`
+    // named `name`, the file may have been renamed since. The first and the
+    // last log of every period are where they were and the others are with
+    // the first, so the periods without logs are the same
+    fn to_chunk(&self, source: usize, name: &str) -> ParsedChunk {
+        let mut log_data: Vec<DateTimeLogLevelMap> = self
+            .logs
+            .iter()
+            .flat_map(|&(datetime, loglevel, count)| {
+                std::iter::repeat_n(
+                    DateTimeLogLevelMap {
+                        datetime,
+                        loglevel,
+                        source,
+                    },
+                    count as usize,
+                )
+            })
+            .collect();
+        let period_logs = log_data.chunk_by_mut(|log, next_log| log.datetime == next_log.datetime);
+        for (logs, &(first, last)) in period_logs.zip(&self.log_spans) {
+            for log in logs.iter_mut() {
+                log.datetime = first;
             }
+            logs[logs.len() - 1].datetime = last;
+        }
+        let pattern_matches = self
+            .pattern_matches
+            .iter()
+            .map(|match_counts| {
+                match_counts
+                    .iter()
+                    .flat_map(|&(datetime, count)| std::iter::repeat_n(datetime, count as usize))
+                    .collect()
+            })
+            .collect();
+
+        return ParsedChunk {
+            log_data,
+            time_span: self.time_span(),
+            parse_failures: self.parse_failures.clone(),
+            pattern_matches,
+            pattern_counter: PatternCounter::from_patterns(self.message_patterns.clone()),
+            rejected_lines: self
+                .rejected_lines
+                .iter()
+                .map(|rejected| RejectedLine {
+                    source: name.to_owned(),
+                    ..rejected.clone()
+                })
+                .collect(),
+            lines_read: self.lines_read,
+            lines_kept: self.lines_kept,
         };
+    }
+}
 
-        let datetime = match NaiveDateTime::parse_from_str(&datetime_str, datetime_format) {
-            Ok(dt) => dt,
-            Err(_) => {
-                eprintln!("Error parsing date in line: {}", log);
-                continue;
+// What a worker found in its span of the lines, the line numbers of the
+// rejected lines are counted from the start of the span
+struct ParsedChunk {
+    log_data: Vec<DateTimeLogLevelMap>,
+    /// Datetimes of the first and the last log, the datetimes of the logs of
+    /// a cache are only the start of their period
+    time_span: Option<(NaiveDateTime, NaiveDateTime)>,
+    parse_failures: ParseFailures,
+    /// Datetimes of the lines matching every pattern rule
+    pattern_matches: Vec<Vec<NaiveDateTime>>,
+    pattern_counter: PatternCounter,
+    rejected_lines: Vec<RejectedLine>,
+    /// Lines in the span, kept by the filters or not
+    lines_read: usize,
+    lines_kept: i32,
+}
+
+impl ParsedChunk {
+    fn new(no_of_patterns: usize) -> ParsedChunk {
+        return ParsedChunk {
+            log_data: Vec::new(),
+            time_span: None,
+            parse_failures: ParseFailures::default(),
+            pattern_matches: vec![Vec::new(); no_of_patterns],
+            pattern_counter: PatternCounter::new(),
+            rejected_lines: Vec::new(),
+            lines_read: 0,
+            lines_kept: 0,
+        };
+    }
+
+    // Appends the chunk of the lines after the ones merged so far, its span
+    // starts after `first_line` lines of its source
+    fn merge(&mut self, other: ParsedChunk, first_line: usize) {
+        self.log_data.extend(other.log_data);
+        self.time_span = join_time_spans(self.time_span, other.time_span);
+        self.parse_failures.merge(&other.parse_failures);
+        for (matches, other_matches) in self.pattern_matches.iter_mut().zip(other.pattern_matches) {
+            matches.extend(other_matches);
+        }
+        self.pattern_counter.merge(other.pattern_counter);
+        self.rejected_lines
+            .extend(other.rejected_lines.into_iter().map(|mut rejected| {
+                rejected.line_number += first_line;
+                rejected
+            }));
+        self.lines_read += other.lines_read;
+        self.lines_kept += other.lines_kept;
+    }
+}
+
+fn join_time_spans(
+    span: Option<(NaiveDateTime, NaiveDateTime)>,
+    other: Option<(NaiveDateTime, NaiveDateTime)>,
+) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    return match (span, other) {
+        (Some((first, last)), Some((other_first, other_last))) => {
+            Some((first.min(other_first), last.max(other_last)))
+        }
+        _ => span.or(other),
+    };
+}
+
+/// Analyses the lines of `log_sources`. With `caches`, which has a cache for
+/// every source, only the lines after the offset of a cache are parsed and
+/// the caches are replaced with ones holding the lines parsed so far. The
+/// message patterns are only counted with `message_patterns`, or with
+/// `caches` as the caches always keep them.
+#[allow(clippy::too_many_arguments)]
+pub fn analyse_logs(
+    log_sources: &[LogSource],
+    caches: Option<&mut [Option<SourceCache>]>,
+    line_filter: &LineFilter,
+    log_parser: &dyn LogParser,
+    gap_options: GapOptions,
+    rules: &[AlertRule],
+    message_patterns: bool,
+    jobs: usize,
+) -> LogsAnalysis {
+    let message_patterns = message_patterns || caches.is_some();
+    let patterns: Vec<&Regex> = rules
+        .iter()
+        .filter_map(|rule| match &rule.target {
+            RuleTarget::Pattern(pattern) => Some(pattern),
+            RuleTarget::Logs(_) => None,
+        })
+        .collect();
+
+    // Bytes of every source to parse and to cache, the last line of a source
+    // is only cached once it is complete
+    let ranges: Vec<(usize, usize)> = log_sources
+        .iter()
+        .enumerate()
+        .map(|(idx, source)| match &caches {
+            Some(caches) => {
+                let start = caches[idx].as_ref().map_or(0, |cache| cache.offset);
+                let end = memrchr(b'\n', &source.bytes).map_or(0, |newline| newline + 1);
+                (start, end.max(start))
             }
+            None => (0, source.bytes.len()),
+        })
+        .collect();
+    let spans = split_into_spans(log_sources, &ranges, jobs);
+    let chunks = map_chunks(&spans, jobs, |_, spans| {
+        spans
+            .iter()
+            .map(|span| {
+                parse_span(
+                    span,
+                    log_sources,
+                    line_filter,
+                    log_parser,
+                    &patterns,
+                    message_patterns,
+                )
+            })
+            .collect::<Vec<ParsedChunk>>()
+    });
+    // Lines of every source parsed by an earlier analysis and now, apart from
+    // its last line when it is not complete yet
+    let mut source_chunks: Vec<(ParsedChunk, Option<ParsedChunk>)> = Vec::new();
+    let mut chunks = chunks.into_iter().flatten().zip(&spans).peekable();
+    for idx in 0..log_sources.len() {
+        let mut source_chunk = match caches.as_deref().and_then(|caches| caches[idx].as_ref()) {
+            Some(cache) => cache.to_chunk(idx, &log_sources[idx].name),
+            None => ParsedChunk::new(patterns.len()),
         };
+        let mut tail: Option<ParsedChunk> = None;
+        while let Some((chunk, span)) = chunks.next_if(|(_, span)| span.source == idx) {
+            if span.tail {
+                tail = Some(chunk);
+            } else {
+                let first_line = source_chunk.lines_read;
+                source_chunk.merge(chunk, first_line);
+            }
+        }
+        source_chunks.push((source_chunk, tail));
+    }
 
-        let loglevel_str = match ll_regex.captures(&log) {
-            Some(caps) => caps.get(0).unwrap().as_str().to_owned(),
-            _ => "others".to_owned(),
+    if let Some(caches) = caches {
+        let time_span = source_chunks
+            .iter()
+            .flat_map(|(chunk, tail)| std::iter::once(chunk).chain(tail))
+            .fold(None, |time_span, chunk| {
+                join_time_spans(time_span, chunk.time_span)
+            });
+        let datetime_cat = time_span.map_or(DateTimeCat::Seconds, |(first, last)| {
+            granularity(last.signed_duration_since(first))
+        });
+        let unit = cache_unit(datetime_cat, gap_options.min_gap);
+        // The buckets become narrower when the logs span a shorter time than
+        // before, eg: once the oldest file is gone, and the gaps when a lower
+        // threshold is given
+        let too_wide: Vec<usize> = (0..caches.len())
+            .filter(|idx| {
+                caches[*idx]
+                    .as_ref()
+                    .is_some_and(|cache| !cache.log_spans.is_empty() && cache.unit > unit)
+            })
+            .collect();
+        if !too_wide.is_empty() {
+            println!(
+                "The logs of {} files were saved in wider periods than the time buckets or the gaps, parsing them again",
+                too_wide.len()
+            );
+            for idx in too_wide {
+                caches[idx] = None;
+            }
+            return analyse_logs(
+                log_sources,
+                Some(caches),
+                line_filter,
+                log_parser,
+                gap_options,
+                rules,
+                message_patterns,
+                jobs,
+            );
+        }
+
+        for (idx, (source_chunk, _)) in source_chunks.iter_mut().enumerate() {
+            trim_rejected_lines(&mut source_chunk.rejected_lines);
+            caches[idx] = Some(SourceCache::new(source_chunk, ranges[idx].1, unit));
+        }
+    }
+
+    let mut merged = ParsedChunk::new(patterns.len());
+    for (mut source_chunk, tail) in source_chunks {
+        if let Some(tail) = tail {
+            let first_line = source_chunk.lines_read;
+            source_chunk.merge(tail, first_line);
+        }
+        merged.merge(source_chunk, 0);
+    }
+    let ParsedChunk {
+        log_data,
+        time_span,
+        parse_failures,
+        pattern_matches,
+        pattern_counter,
+        mut rejected_lines,
+        lines_kept: no_of_lines,
+        ..
+    } = merged;
+    // Every worker kept its own sample of the rejected lines
+    trim_rejected_lines(&mut rejected_lines);
+
+    if log_data.is_empty() {
+        println!("No logs with a valid datetime found");
+        let empty_aggregate = LogsAggregate {
+            datetimes: Vec::new(),
+            datetime_cat: DateTimeCat::Seconds,
+            aggregates: make_loglevel_count_vec_map(),
+        };
+        return LogsAnalysis {
+            metrics: compute_metrics(&empty_aggregate),
+            gaps: detect_gaps(
+                &log_data,
+                time_span,
+                log_sources,
+                &empty_aggregate,
+                gap_options,
+            ),
+            rule_firings: (!rules.is_empty()).then(Vec::new),
+            message_patterns: pattern_counter.top(TOP_PATTERNS),
+            logs_aggregate: empty_aggregate,
+            parse_failures,
+            hour_weekday_counts: count_by_hour_and_weekday(&log_data),
+            time_span: None,
+            rejected_lines,
+            no_of_lines,
         };
+    }
+
+    let (mindt, maxdt) = &time_span.unwrap();
+    let logs_duration = maxdt.signed_duration_since(*mindt);
 
-        let loglevel = to_loglevel(&loglevel_str);
+    let logs_aggregate = match granularity(logs_duration) {
+        DateTimeCat::Years => {
+            println!("Analysing in terms of years...");
+            let duration_years =
+                u64::div_ceil(logs_duration.num_seconds() as u64, YEAR_SECONDS as u64) as i64;
 
-        unq_datetimes.insert(datetime);
-        log_data.push(DateTimeLogLevelMap { datetime, loglevel });
+            aggregate_in_terms_of_years(&log_data, mindt, maxdt, duration_years, jobs)
+        }
+        DateTimeCat::Months => {
+            println!("Analysing in terms of months...");
+            let duration_months =
+                u64::div_ceil(logs_duration.num_seconds() as u64, MONTH_SECONDS as u64) as i64;
+
+            aggregate_in_terms_of_months(&log_data, mindt, maxdt, duration_months, jobs)
+        }
+        DateTimeCat::Days => {
+            println!("Analysing in terms of days...");
+            let duration_days =
+                u64::div_ceil(logs_duration.num_seconds() as u64, DAY_SECONDS as u64) as i64;
+
+            aggregate_in_terms_of_days(&log_data, mindt, maxdt, duration_days, jobs)
+        }
+        DateTimeCat::Hours => {
+            println!("Analysing in terms of hours...");
+            let duration_hours =
+                u64::div_ceil(logs_duration.num_seconds() as u64, HOUR_SECONDS as u64) as i64;
+
+            aggregate_in_terms_of_hours(&log_data, mindt, maxdt, duration_hours, jobs)
+        }
+        DateTimeCat::Minutes => {
+            println!("Analysing in terms of minutes...");
+            let duration_mins =
+                u64::div_ceil(logs_duration.num_seconds() as u64, MIN_SECONDS as u64) as i64;
+
+            aggregate_in_terms_of_minutes(&log_data, mindt, maxdt, duration_mins, jobs)
+        }
+        DateTimeCat::Seconds => {
+            println!("Analysing in terms of seconds...");
+            let duration_secs = if logs_duration.subsec_nanos() > 0 {
+                logs_duration.num_seconds() + 1
+            } else {
+                logs_duration.num_seconds()
+            };
+
+            aggregate_in_terms_of_seconds(&log_data, mindt, maxdt, duration_secs, jobs)
+        }
+    };
+
+    return LogsAnalysis {
+        metrics: compute_metrics(&logs_aggregate),
+        gaps: detect_gaps(
+            &log_data,
+            time_span,
+            log_sources,
+            &logs_aggregate,
+            gap_options,
+        ),
+        rule_firings: (!rules.is_empty())
+            .then(|| evaluate_rules(rules, &logs_aggregate, &pattern_matches)),
+        message_patterns: pattern_counter.top(TOP_PATTERNS),
+        logs_aggregate,
+        parse_failures,
+        hour_weekday_counts: count_by_hour_and_weekday(&log_data),
+        time_span,
+        rejected_lines,
+        no_of_lines,
+    };
+}
+
+// Width of the time buckets of logs spanning `logs_duration`
+fn granularity(logs_duration: TimeDelta) -> DateTimeCat {
+    let secs = logs_duration.num_seconds();
+    if secs > YEAR_SECONDS {
+        return DateTimeCat::Years;
+    } else if secs > MONTH_SECONDS {
+        return DateTimeCat::Months;
+    } else if secs > DAY_SECONDS {
+        return DateTimeCat::Days;
+    } else if secs > HOUR_SECONDS {
+        return DateTimeCat::Hours;
+    } else if secs > MIN_SECONDS {
+        return DateTimeCat::Minutes;
     }
+    return DateTimeCat::Seconds;
+}
 
-    let mindt = unq_datetimes.iter().min().unwrap();
-    let maxdt = unq_datetimes.iter().max().unwrap();
+// Widest periods the logs of a cache can be counted in. The buckets start
+// with a period, so the logs still fall in the same buckets, and so do the
+// hours of the day. Periods no longer than `min_gap` keep the gaps found in
+// the logs to within a period, and every log inside a gap.
+fn cache_unit(datetime_cat: DateTimeCat, min_gap: Option<TimeDelta>) -> DateTimeCat {
+    let max_secs = min_gap.map_or(i64::MAX, |min_gap| min_gap.num_seconds());
+    return [
+        (DateTimeCat::Hours, HOUR_SECONDS),
+        (DateTimeCat::Minutes, MIN_SECONDS),
+    ]
+    .into_iter()
+    .find(|(unit, unit_secs)| *unit <= datetime_cat && *unit_secs <= max_secs)
+    .map_or(DateTimeCat::Seconds, |(unit, _)| unit);
+}
 
-    let logs_duration = maxdt.signed_duration_since(mindt.to_owned());
+// Keeps the first REJECTS_SAMPLE_SIZE rejected lines of every reason
+fn trim_rejected_lines(rejected_lines: &mut Vec<RejectedLine>) {
+    let mut no_of_rejects: HashMap<LineParseError, i32> = HashMap::new();
+    rejected_lines.retain(|rejected| {
+        let count = no_of_rejects.entry(rejected.reason).or_insert(0);
+        *count += 1;
+        *count <= REJECTS_SAMPLE_SIZE
+    });
+}
 
-    if logs_duration.num_seconds() > YEAR_SECONDS {
-        println!("Analysing in terms of years...");
-        let duration_years =
-            u64::div_ceil(logs_duration.num_seconds() as u64, YEAR_SECONDS as u64) as i64;
+// Splits the given range of every source at line endings into spans of about
+// the same size, so the lines of a large file are parsed by several workers.
+// What follows the range is the tail of the source.
+fn split_into_spans<'a>(
+    log_sources: &'a [LogSource],
+    ranges: &[(usize, usize)],
+    jobs: usize,
+) -> Vec<Span<'a>> {
+    let total_bytes: usize = log_sources
+        .iter()
+        .zip(ranges)
+        .map(|(source, (start, _))| source.bytes.len() - start)
+        .sum();
+    let span_size = (total_bytes / jobs.max(1)).max(MIN_SPAN_BYTES);
 
-        logs_aggregate = aggregate_in_terms_of_years(&log_data, mindt, maxdt, duration_years);
-    } else if logs_duration.num_seconds() > MONTH_SECONDS {
-        println!("Analysing in terms of months...");
-        let duration_months =
-            u64::div_ceil(logs_duration.num_seconds() as u64, MONTH_SECONDS as u64) as i64;
+    let mut spans: Vec<Span> = Vec::new();
+    for (idx, (source, &(start, end))) in log_sources.iter().zip(ranges).enumerate() {
+        let bytes: &[u8] = &source.bytes[..end];
+        let mut span_start = start;
+        while span_start < bytes.len() {
+            let span_end = match bytes.get(span_start + span_size..) {
+                Some(rest) => memchr(b'\n', rest)
+                    .map(|newline| span_start + span_size + newline + 1)
+                    .unwrap_or(bytes.len()),
+                None => bytes.len(),
+            };
+            spans.push(Span {
+                source: idx,
+                bytes: &bytes[span_start..span_end],
+                tail: false,
+            });
+            span_start = span_end;
+        }
+        if end < source.bytes.len() {
+            spans.push(Span {
+                source: idx,
+                bytes: &source.bytes[end..],
+                tail: true,
+            });
+        }
+    }
 
-        logs_aggregate = aggregate_in_terms_of_months(&log_data, mindt, maxdt, duration_months);
-    } else if logs_duration.num_seconds() > DAY_SECONDS {
-        println!("Analysing in terms of days...");
-        let duration_days =
-            u64::div_ceil(logs_duration.num_seconds() as u64, DAY_SECONDS as u64) as i64;
+    return spans;
+}
 
-        logs_aggregate = aggregate_in_terms_of_days(&log_data, mindt, maxdt, duration_days);
-    } else if logs_duration.num_seconds() > HOUR_SECONDS {
-        println!("Analysing in terms of hours...");
-        let duration_hours =
-            u64::div_ceil(logs_duration.num_seconds() as u64, HOUR_SECONDS as u64) as i64;
+fn parse_span(
+    span: &Span,
+    log_sources: &[LogSource],
+    line_filter: &LineFilter,
+    log_parser: &dyn LogParser,
+    patterns: &[&Regex],
+    message_patterns: bool,
+) -> ParsedChunk {
+    let source_parser = log_sources[span.source]
+        .modified
+        .and_then(|modified| log_parser.for_source(modified));
+    let log_parser = source_parser.as_deref().unwrap_or(log_parser);
+    let mut chunk = ParsedChunk::new(patterns.len());
+    for (idx, line) in byte_lines(span.bytes).enumerate() {
+        chunk.lines_read += 1;
+        if !line_filter.keeps_bytes(line) {
+            continue;
+        }
+        chunk.lines_kept += 1;
 
-        logs_aggregate = aggregate_in_terms_of_hours(&log_data, mindt, maxdt, duration_hours);
-    } else if logs_duration.num_seconds() > MIN_SECONDS {
-        println!("Analysing in terms of minutes...");
-        let duration_mins =
-            u64::div_ceil(logs_duration.num_seconds() as u64, MIN_SECONDS as u64) as i64;
+        let LogRecord {
+            datetime,
+            loglevel,
+            message,
+            ..
+        } = match log_parser.parse_bytes(line) {
+            Ok(record) => record,
+            Err(reason) => {
+                let failures = match reason {
+                    LineParseError::NoDatetimeMatch => &mut chunk.parse_failures.no_datetime_match,
+                    LineParseError::DatetimeParseError => {
+                        &mut chunk.parse_failures.datetime_parse_error
+                    }
+                    LineParseError::MalformedRecord => &mut chunk.parse_failures.malformed_record,
+                };
+                *failures += 1;
+                if *failures <= REJECTS_SAMPLE_SIZE {
+                    chunk.rejected_lines.push(RejectedLine {
+                        source: log_sources[span.source].name.clone(),
+                        line_number: idx + 1,
+                        reason,
+                        line: String::from_utf8_lossy(line).into_owned(),
+                    });
+                }
+                continue;
+            }
+        };
+        if loglevel == LogLevel::Others {
+            chunk.parse_failures.level_not_found += 1;
+        }
+
+        if !patterns.is_empty() {
+            let line = String::from_utf8_lossy(line);
+            for (idx, pattern) in patterns.iter().enumerate() {
+                if pattern.is_match(&line) {
+                    chunk.pattern_matches[idx].push(datetime);
+                }
+            }
+        }
 
-        logs_aggregate = aggregate_in_terms_of_minutes(&log_data, mindt, maxdt, duration_mins);
-    } else {
-        println!("Analysing in terms of seconds...");
-        let duration_secs: i64;
-        if logs_duration.subsec_nanos() > 0 {
-            duration_secs = logs_duration.num_seconds() + 1;
-        } else {
-            duration_secs = logs_duration.num_seconds();
+        if message_patterns {
+            chunk.pattern_counter.add(loglevel, &message);
         }
+        chunk.time_span = join_time_spans(chunk.time_span, Some((datetime, datetime)));
+        chunk.log_data.push(DateTimeLogLevelMap {
+            datetime,
+            loglevel,
+            source: span.source,
+        });
+    }
+
+    return chunk;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeDelta};
+    use serde_json::{json, Value};
+
+    use super::MIN_SPAN_BYTES;
+    use crate::{Analysis, AnalysisConfig, TextSource};
+
+    fn analyse_with_jobs(source: &TextSource, jobs: usize) -> Analysis {
+        return AnalysisConfig::builder("%Y-%m-%d %H:%M:%S")
+            .jobs(jobs)
+            .build()
+            .unwrap()
+            .analyse(source)
+            .unwrap();
+    }
 
-        logs_aggregate = aggregate_in_terms_of_seconds(&log_data, mindt, maxdt, duration_secs);
+    fn findings(analysis: &Analysis) -> Value {
+        let logs_analysis = &analysis.logs_analysis;
+        let logs_aggregate = &logs_analysis.logs_aggregate;
+        let mut aggregates: Vec<_> = logs_aggregate.aggregates.iter().collect();
+        aggregates.sort_unstable();
+        return json!({
+            "no_of_logs": analysis.no_of_logs,
+            "datetimes": logs_aggregate.datetimes,
+            "aggregates": aggregates,
+            "parse_failures": logs_analysis.parse_failures,
+            "rejected_lines": logs_analysis.rejected_lines,
+            "message_patterns": logs_analysis.message_patterns,
+            "gaps": logs_analysis.gaps,
+        });
     }
 
-    return logs_aggregate;
+    #[test]
+    fn several_workers_find_the_same_as_one() {
+        let start =
+            NaiveDateTime::parse_from_str("2024-03-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let levels = ["INFO", "DEBUG", "WARN", "ERROR", "notice"];
+        let mut text = String::new();
+        let mut idx: i64 = 0;
+        while text.len() < 4 * MIN_SPAN_BYTES + 1000 {
+            let datetime = start + TimeDelta::seconds(idx);
+            let line = match idx % 1999 {
+                7 => "  continuation without a datetime".to_owned(),
+                11 => format!("{} no such day", datetime.format("2024-02-30 %H:%M:%S")),
+                _ => format!(
+                    "{} [{}] worker-{} handled request {}",
+                    datetime.format("%Y-%m-%d %H:%M:%S"),
+                    levels[idx as usize % levels.len()],
+                    idx % 8,
+                    idx
+                ),
+            };
+            // Some of the lines end with CRLF, and the last one has no line
+            // ending at all
+            text += &line;
+            text += if idx % 3 == 0 { "\r\n" } else { "\n" };
+            idx += 1;
+        }
+        text += "2024-03-01 23:59:59 [ERROR] last line";
+        let source = TextSource {
+            name: "big.log".to_owned(),
+            text,
+        };
+
+        let one_worker = analyse_with_jobs(&source, 1);
+        let parse_failures = &one_worker.logs_analysis.parse_failures;
+        assert_eq!(one_worker.no_of_logs as i64, idx + 1);
+        assert!(parse_failures.no_datetime_match > 30);
+        assert!(parse_failures.datetime_parse_error > 30);
+        // Rejected lines are numbered from the start of the file
+        for rejected in &one_worker.logs_analysis.rejected_lines {
+            let idx = rejected.line_number as i64 - 1;
+            let expected = match idx % 1999 {
+                7 => "  continuation without a datetime".to_owned(),
+                11 => format!(
+                    "{} no such day",
+                    (start + TimeDelta::seconds(idx)).format("2024-02-30 %H:%M:%S")
+                ),
+                _ => panic!("line {} was rejected", rejected.line_number),
+            };
+            assert_eq!(rejected.line, expected);
+        }
+        for jobs in [2, 4] {
+            assert_eq!(
+                findings(&analyse_with_jobs(&source, jobs)),
+                findings(&one_worker),
+                "{} jobs",
+                jobs
+            );
+        }
+    }
 }
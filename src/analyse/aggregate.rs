@@ -4,15 +4,17 @@ use chrono::{Datelike, Months, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
 use std::collections::HashMap;
 
 use crate::analyse::loglevel::{make_loglevel_count_map, make_loglevel_count_vec_map, LogLevel};
-use crate::analyse::{DateTimeLogLevelMap, LogsAggregate};
+use crate::analyse::{DateTimeLogLevelMap, HourWeekdayCounts, LogsAggregate};
+use crate::parallel::map_chunks;
 
 use super::dtfmt::DateTimeCat;
 
 pub fn aggregate_in_terms_of_seconds(
-    log_data: &Vec<DateTimeLogLevelMap>,
+    log_data: &[DateTimeLogLevelMap],
     start_datetime: &NaiveDateTime,
     end_datetime: &NaiveDateTime,
     duration_secs: i64,
+    jobs: usize,
 ) -> LogsAggregate {
     let cur_datetime = start_datetime.to_owned();
     let mut datetime_secs: Vec<NaiveDateTime> = Vec::new();
@@ -25,7 +27,7 @@ pub fn aggregate_in_terms_of_seconds(
         datetime_secs.push(inc_datetime);
     }
 
-    let analyzed_data = aggregate_logs(&datetime_secs, log_data);
+    let analyzed_data = aggregate_logs(&datetime_secs, log_data, jobs);
 
     let (filtered_datetimes, filtered_data) =
         clean_analyzed_data(&datetime_secs, &analyzed_data, end_datetime);
@@ -38,10 +40,11 @@ pub fn aggregate_in_terms_of_seconds(
 }
 
 pub fn aggregate_in_terms_of_minutes(
-    log_data: &Vec<DateTimeLogLevelMap>,
+    log_data: &[DateTimeLogLevelMap],
     start_datetime: &NaiveDateTime,
     end_datetime: &NaiveDateTime,
     duration_mins: i64,
+    jobs: usize,
 ) -> LogsAggregate {
     let mut datetime_mins: Vec<NaiveDateTime> = Vec::new();
 
@@ -58,7 +61,7 @@ pub fn aggregate_in_terms_of_minutes(
         datetime_mins.push(inc_datetime);
     }
 
-    let analyzed_data = aggregate_logs(&datetime_mins, log_data);
+    let analyzed_data = aggregate_logs(&datetime_mins, log_data, jobs);
 
     let (filtered_datetimes, filtered_data) =
         clean_analyzed_data(&datetime_mins, &analyzed_data, end_datetime);
@@ -71,10 +74,11 @@ pub fn aggregate_in_terms_of_minutes(
 }
 
 pub fn aggregate_in_terms_of_hours(
-    log_data: &Vec<DateTimeLogLevelMap>,
+    log_data: &[DateTimeLogLevelMap],
     start_datetime: &NaiveDateTime,
     end_datetime: &NaiveDateTime,
     duration_hours: i64,
+    jobs: usize,
 ) -> LogsAggregate {
     let mut datetime_hrs: Vec<NaiveDateTime> = Vec::new();
 
@@ -93,7 +97,7 @@ pub fn aggregate_in_terms_of_hours(
         datetime_hrs.push(inc_datetime);
     }
 
-    let analyzed_data = aggregate_logs(&datetime_hrs, log_data);
+    let analyzed_data = aggregate_logs(&datetime_hrs, log_data, jobs);
 
     let (filtered_datetimes, filtered_data) =
         clean_analyzed_data(&datetime_hrs, &analyzed_data, end_datetime);
@@ -106,10 +110,11 @@ pub fn aggregate_in_terms_of_hours(
 }
 
 pub fn aggregate_in_terms_of_days(
-    log_data: &Vec<DateTimeLogLevelMap>,
+    log_data: &[DateTimeLogLevelMap],
     start_datetime: &NaiveDateTime,
     end_datetime: &NaiveDateTime,
     duration_days: i64,
+    jobs: usize,
 ) -> LogsAggregate {
     let mut datetime_days: Vec<NaiveDateTime> = Vec::new();
 
@@ -124,7 +129,7 @@ pub fn aggregate_in_terms_of_days(
             .unwrap();
         datetime_days.push(inc_datetime);
     }
-    let analyzed_data = aggregate_logs(&datetime_days, log_data);
+    let analyzed_data = aggregate_logs(&datetime_days, log_data, jobs);
 
     let (filtered_datetimes, filtered_data) =
         clean_analyzed_data(&datetime_days, &analyzed_data, end_datetime);
@@ -137,10 +142,11 @@ pub fn aggregate_in_terms_of_days(
 }
 
 pub fn aggregate_in_terms_of_months(
-    log_data: &Vec<DateTimeLogLevelMap>,
+    log_data: &[DateTimeLogLevelMap],
     start_datetime: &NaiveDateTime,
     end_datetime: &NaiveDateTime,
     duration_months: i64,
+    jobs: usize,
 ) -> LogsAggregate {
     let mut datetime_mnths: Vec<NaiveDateTime> = Vec::new();
 
@@ -157,7 +163,7 @@ pub fn aggregate_in_terms_of_months(
             .unwrap();
         datetime_mnths.push(inc_datetime);
     }
-    let analyzed_data = aggregate_logs(&datetime_mnths, log_data);
+    let analyzed_data = aggregate_logs(&datetime_mnths, log_data, jobs);
 
     let (filtered_datetimes, filtered_data) =
         clean_analyzed_data(&datetime_mnths, &analyzed_data, end_datetime);
@@ -170,10 +176,11 @@ pub fn aggregate_in_terms_of_months(
 }
 
 pub fn aggregate_in_terms_of_years(
-    log_data: &Vec<DateTimeLogLevelMap>,
+    log_data: &[DateTimeLogLevelMap],
     start_datetime: &NaiveDateTime,
     end_datetime: &NaiveDateTime,
     duration_years: i64,
+    jobs: usize,
 ) -> LogsAggregate {
     let mut datetime_yrs: Vec<NaiveDateTime> = Vec::new();
 
@@ -192,7 +199,7 @@ pub fn aggregate_in_terms_of_years(
             .unwrap();
         datetime_yrs.push(inc_datetime);
     }
-    let analyzed_data = aggregate_logs(&datetime_yrs, log_data);
+    let analyzed_data = aggregate_logs(&datetime_yrs, log_data, jobs);
 
     let (filtered_datetimes, filtered_data) =
         clean_analyzed_data(&datetime_yrs, &analyzed_data, end_datetime);
@@ -204,49 +211,47 @@ pub fn aggregate_in_terms_of_years(
     };
 }
 
+// Every worker counts the logs of its part of `log_data` in every bucket,
+// and their partial counts are added up
 fn aggregate_logs(
-    datetimes: &Vec<NaiveDateTime>,
-    log_data: &Vec<DateTimeLogLevelMap>,
+    datetimes: &[NaiveDateTime],
+    log_data: &[DateTimeLogLevelMap],
+    jobs: usize,
 ) -> HashMap<LogLevel, Vec<i32>> {
-    let mut analyzed_data: HashMap<LogLevel, Vec<i32>> = make_loglevel_count_vec_map();
-
-    for i in 0..datetimes.len() - 1 {
-        let datetime = datetimes[i];
-        let next_datetime = datetimes[i + 1];
-        let counts = count_log_levels(log_data, &datetime, &next_datetime);
-        for (loglevel, data) in analyzed_data.to_owned() {
-            let mut updated_data = data.to_vec();
-            updated_data.push(counts[&loglevel]);
-            analyzed_data.insert(loglevel, updated_data);
+    let no_of_buckets = datetimes.len() - 1;
+    let partial_counts = map_chunks(log_data, jobs, |_, logs| {
+        let mut counts: HashMap<LogLevel, Vec<i32>> = make_loglevel_count_vec_map();
+        for counts in counts.values_mut() {
+            counts.resize(no_of_buckets, 0);
         }
-    }
-
-    return analyzed_data;
-}
-
-fn count_log_levels(
-    log_data: &Vec<DateTimeLogLevelMap>,
-    from_dt: &NaiveDateTime,
-    to_dt: &NaiveDateTime,
-) -> HashMap<LogLevel, i32> {
-    let mut loglevel_counts: HashMap<LogLevel, i32> = make_loglevel_count_map();
-
-    for log in log_data {
-        if log.datetime >= from_dt.to_owned() && log.datetime < to_dt.to_owned() {
-            for (loglevel, count) in loglevel_counts.to_owned() {
-                if log.loglevel == loglevel {
-                    loglevel_counts.insert(loglevel, count + 1);
-                    break;
-                }
+        for log in logs {
+            // Index of the last bucket starting at or before the log
+            let bucket = datetimes.partition_point(|datetime| *datetime <= log.datetime);
+            if bucket == 0 || bucket > no_of_buckets {
+                continue;
+            }
+            if let Some(counts) = counts.get_mut(&log.loglevel) {
+                counts[bucket - 1] += 1;
+            }
+        }
+        counts
+    });
+
+    let mut partial_counts = partial_counts.into_iter();
+    let mut analyzed_data = partial_counts.next().unwrap();
+    for counts in partial_counts {
+        for (loglevel, data) in analyzed_data.iter_mut() {
+            for (count, partial_count) in data.iter_mut().zip(&counts[loglevel]) {
+                *count += partial_count;
             }
         }
     }
 
-    return loglevel_counts;
+    return analyzed_data;
 }
 
 fn clean_analyzed_data(
-    datetimes: &Vec<NaiveDateTime>,
+    datetimes: &[NaiveDateTime],
     analyzed_data: &HashMap<LogLevel, Vec<i32>>,
     end_datetime: &NaiveDateTime,
 ) -> (Vec<NaiveDateTime>, HashMap<LogLevel, Vec<i32>>) {
@@ -264,3 +269,22 @@ fn clean_analyzed_data(
 
     return (filtered_datetimes, loglevel_counts);
 }
+
+pub fn count_by_hour_and_weekday(
+    log_data: &[DateTimeLogLevelMap],
+) -> HashMap<LogLevel, HourWeekdayCounts> {
+    let mut hour_weekday_counts: HashMap<LogLevel, HourWeekdayCounts> = make_loglevel_count_map()
+        .into_keys()
+        .map(|loglevel| (loglevel, [[0; 24]; 7]))
+        .collect();
+
+    for log in log_data {
+        let weekday = log.datetime.weekday().num_days_from_monday() as usize;
+        let hour = log.datetime.hour() as usize;
+        if let Some(counts) = hour_weekday_counts.get_mut(&log.loglevel) {
+            counts[weekday][hour] += 1;
+        }
+    }
+
+    return hour_weekday_counts;
+}
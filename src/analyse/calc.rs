@@ -6,7 +6,7 @@ use crate::analyse::loglevel::LogLevel;
 
 pub fn max_log_occ(loglevel_count: &HashMap<LogLevel, Vec<i32>>, loglevel: &LogLevel) -> i32 {
     let log_counts = &loglevel_count[loglevel];
-    let max_logs_count = log_counts.iter().max().unwrap().to_owned();
+    let max_logs_count = log_counts.iter().max().unwrap_or(&0).to_owned();
     return max_logs_count;
 }
 
@@ -22,12 +22,5 @@ pub fn sum_of_log_occ(loglevel_count: &HashMap<LogLevel, Vec<i32>>) -> HashMap<L
 }
 
 pub fn max_log_count(loglevel_count: &HashMap<LogLevel, i32>) -> i32 {
-    return loglevel_count
-        .iter()
-        .map(|(_, count)| {
-            return count;
-        })
-        .max()
-        .unwrap()
-        .to_owned();
+    return loglevel_count.values().max().unwrap().to_owned();
 }
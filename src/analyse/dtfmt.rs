@@ -1,8 +1,12 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta, Timelike};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+/// Width of a time bucket, from the narrowest to the widest
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DateTimeCat {
     Seconds,
     Minutes,
@@ -19,6 +23,11 @@ const DATETIME_DAY_FMT: &str = "%Y-%m-%d";
 const DATETIME_MTH_FMT: &str = "%Y-%b";
 const DATETIME_YR_FMT: &str = "%Y";
 
+/// Format of the first and last log datetimes in the report metadata
+pub const TIME_SPAN_FMT: &str = DATETIME_SECS_FMT;
+/// Format of the time a report was generated at
+pub const GENERATED_AT_FMT: &str = "%Y-%m-%d %H:%M:%S %:z";
+
 pub fn get_dt_fmt(dt_cat: &DateTimeCat) -> String {
     return match dt_cat {
         DateTimeCat::Seconds => String::from(DATETIME_SECS_FMT),
@@ -30,6 +39,37 @@ pub fn get_dt_fmt(dt_cat: &DateTimeCat) -> String {
     };
 }
 
+/// Start of the bucket of width `dt_cat` holding `datetime`, the buckets
+/// start with a second, minute, hour, day, month or year.
+pub fn bucket_start(datetime: NaiveDateTime, dt_cat: DateTimeCat) -> NaiveDateTime {
+    let date = datetime.date();
+    let start = match dt_cat {
+        DateTimeCat::Seconds => return datetime.with_nanosecond(0).unwrap(),
+        DateTimeCat::Minutes => date.and_hms_opt(datetime.hour(), datetime.minute(), 0),
+        DateTimeCat::Hours => date.and_hms_opt(datetime.hour(), 0, 0),
+        DateTimeCat::Days => date.and_hms_opt(0, 0, 0),
+        DateTimeCat::Months => date.with_day(1).unwrap().and_hms_opt(0, 0, 0),
+        DateTimeCat::Years => NaiveDate::from_ymd_opt(date.year(), 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0),
+    };
+    return start.unwrap();
+}
+
+// Formats for the labels on a time axis, the first one is used for every
+// labelled bucket and the second one marks where a larger unit begins, eg:
+// the day above the hour labels.
+pub fn get_axis_dt_fmts(dt_cat: &DateTimeCat) -> (&'static str, Option<&'static str>) {
+    return match dt_cat {
+        DateTimeCat::Seconds => ("%H:%M:%S", Some(DATETIME_DAY_FMT)),
+        DateTimeCat::Minutes => ("%H:%M", Some(DATETIME_DAY_FMT)),
+        DateTimeCat::Hours => ("%H:%M", Some(DATETIME_DAY_FMT)),
+        DateTimeCat::Days => ("%d", Some(DATETIME_MTH_FMT)),
+        DateTimeCat::Months => ("%b", Some(DATETIME_YR_FMT)),
+        DateTimeCat::Years => (DATETIME_YR_FMT, None),
+    };
+}
+
 pub fn dt_fmt_to_regex(dt_str: &str) -> Regex {
     // Supported specs
     // %Y %y %m %b %B %h %d %e %F %H %k %I %l %P %p %M %S
@@ -67,3 +107,50 @@ pub fn dt_fmt_to_regex(dt_str: &str) -> Regex {
 
     return re;
 }
+
+/// Parses durations like "30s", "10m", "2h" or "1d", a number without a unit
+/// is in seconds.
+pub fn parse_duration(duration: &str) -> Result<TimeDelta, String> {
+    let duration = duration.trim();
+    let unit_start = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (value, unit) = duration.split_at(unit_start);
+    let value: i64 = value
+        .parse()
+        .map_err(|_| format!("Duration should look like 10m, got '{}'", duration))?;
+
+    let delta = match unit {
+        "s" | "" => TimeDelta::try_seconds(value),
+        "m" => TimeDelta::try_minutes(value),
+        "h" => TimeDelta::try_hours(value),
+        "d" => TimeDelta::try_days(value),
+        _ => {
+            return Err(format!(
+                "Invalid duration unit '{}', use one of s, m, h or d",
+                unit
+            ))
+        }
+    };
+
+    return delta.ok_or(format!("Duration '{}' is too long", duration));
+}
+
+/// Formats a duration like "1d 2h 5m", leaving out the zero units.
+pub fn format_duration(secs: i64) -> String {
+    let units = [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+    let mut remaining = secs;
+    let parts: Vec<String> = units
+        .iter()
+        .filter_map(|(unit, unit_secs)| {
+            let value = remaining / unit_secs;
+            remaining %= unit_secs;
+            (value > 0).then(|| format!("{}{}", value, unit))
+        })
+        .collect();
+
+    if parts.is_empty() {
+        return "0s".to_owned();
+    }
+    return parts.join(" ");
+}
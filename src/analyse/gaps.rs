@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::iter::once;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::Serialize;
+
+use crate::analyse::{metrics::bucket_end, DateTimeLogLevelMap, LogSource, LogsAggregate};
+
+/// Name of the group holding the logs of all the files together
+pub const ALL_SOURCES: &str = "all files";
+
+#[derive(Copy, Clone)]
+pub struct GapOptions {
+    /// Shortest period worth reporting, two buckets long when not given
+    pub min_gap: Option<TimeDelta>,
+    /// Share of the usual log rate below which a bucket counts as silent
+    pub silence_ratio: f64,
+}
+
+#[derive(Serialize)]
+pub struct GapAnalysis {
+    pub min_gap_secs: i64,
+    pub silence_ratio: f64,
+    pub gaps: Vec<Gap>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GapKind {
+    /// Not a single log in the period
+    NoLogs,
+    /// Some logs, but far fewer than usual
+    LowVolume,
+}
+
+#[derive(Serialize)]
+pub struct Gap {
+    /// File the gap was found in, or `ALL_SOURCES` for all the files together
+    pub source: String,
+    pub kind: GapKind,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub duration_secs: i64,
+}
+
+pub(super) fn detect_gaps(
+    log_data: &[DateTimeLogLevelMap],
+    time_span: Option<(NaiveDateTime, NaiveDateTime)>,
+    log_sources: &[LogSource],
+    logs_aggregate: &LogsAggregate,
+    options: GapOptions,
+) -> GapAnalysis {
+    let min_gap = match options.min_gap {
+        Some(min_gap) => min_gap,
+        None if logs_aggregate.datetimes.is_empty() => TimeDelta::zero(),
+        None => {
+            let first_bucket_end = bucket_end(logs_aggregate, 0);
+            first_bucket_end.signed_duration_since(logs_aggregate.datetimes[0]) * 2
+        }
+    };
+    let mut gaps: Vec<Gap> = Vec::new();
+
+    if let Some((mindt, maxdt)) = time_span {
+        // The logs of every source are gathered in a single pass, there can
+        // be hundreds of rotated files
+        let mut groups: Vec<(&str, Vec<NaiveDateTime>)> = log_sources
+            .iter()
+            .map(|source| (source.name.as_str(), Vec::new()))
+            .collect();
+        for log in log_data {
+            groups[log.source].1.push(log.datetime);
+        }
+        if log_sources.len() > 1 {
+            groups.push((
+                ALL_SOURCES,
+                log_data.iter().map(|log| log.datetime).collect(),
+            ));
+        }
+
+        for (source, mut datetimes) in groups {
+            // Sources without a single valid log are most likely in another format
+            if datetimes.is_empty() {
+                continue;
+            }
+            datetimes.sort_unstable();
+
+            find_silent_periods(source, &datetimes, (mindt, maxdt), min_gap, &mut gaps);
+            find_low_volume_periods(
+                source,
+                &datetimes,
+                logs_aggregate,
+                (mindt, maxdt),
+                min_gap,
+                options.silence_ratio,
+                &mut gaps,
+            );
+        }
+    }
+
+    gaps.sort_by(|a, b| a.source.cmp(&b.source).then(a.start.cmp(&b.start)));
+
+    return GapAnalysis {
+        min_gap_secs: min_gap.num_seconds(),
+        silence_ratio: options.silence_ratio,
+        gaps,
+    };
+}
+
+// A source that starts late or stops early is silent until the first or after
+// the last log of all the sources, so the whole span is checked.
+fn find_silent_periods(
+    source: &str,
+    datetimes: &[NaiveDateTime],
+    span: (NaiveDateTime, NaiveDateTime),
+    min_gap: TimeDelta,
+    gaps: &mut Vec<Gap>,
+) {
+    let mut prev = span.0;
+    for datetime in datetimes.iter().copied().chain(once(span.1)) {
+        if datetime.signed_duration_since(prev) > min_gap {
+            gaps.push(make_gap(source, GapKind::NoLogs, prev, datetime));
+        }
+        prev = prev.max(datetime);
+    }
+}
+
+// Buckets are compared by their rate of logs rather than the count, as the
+// first and the last bucket are usually only partly covered by the logs.
+fn find_low_volume_periods(
+    source: &str,
+    datetimes: &[NaiveDateTime],
+    logs_aggregate: &LogsAggregate,
+    span: (NaiveDateTime, NaiveDateTime),
+    min_gap: TimeDelta,
+    silence_ratio: f64,
+    gaps: &mut Vec<Gap>,
+) {
+    let num_dt = logs_aggregate.datetimes.len();
+    let mut counts = vec![0; num_dt];
+    for datetime in datetimes {
+        let idx = logs_aggregate
+            .datetimes
+            .partition_point(|bucket| bucket <= datetime);
+        counts[idx.saturating_sub(1)] += 1;
+    }
+
+    let rates: Vec<f64> = (0..num_dt)
+        .map(|idx| {
+            let start = logs_aggregate.datetimes[idx].max(span.0);
+            let end = bucket_end(logs_aggregate, idx).min(span.1);
+            let covered_secs = end.signed_duration_since(start).num_seconds().max(1);
+            counts[idx] as f64 / covered_secs as f64
+        })
+        .collect();
+
+    let mut active_rates: Vec<f64> = (0..num_dt)
+        .filter(|idx| counts[*idx] > 0)
+        .map(|idx| rates[idx])
+        .collect();
+    if active_rates.is_empty() {
+        return;
+    }
+    active_rates.sort_unstable_by(f64::total_cmp);
+    let min_rate = active_rates[active_rates.len() / 2] * silence_ratio;
+
+    // Empty buckets are already covered by the periods without logs
+    let mut run_start: Option<usize> = None;
+    for idx in 0..=num_dt {
+        let is_low = idx < num_dt && counts[idx] > 0 && rates[idx] < min_rate;
+        match (is_low, run_start) {
+            (true, None) => run_start = Some(idx),
+            (false, Some(first)) => {
+                let start = logs_aggregate.datetimes[first].max(span.0);
+                let end = bucket_end(logs_aggregate, idx - 1).min(span.1);
+                if end.signed_duration_since(start) >= min_gap {
+                    gaps.push(make_gap(source, GapKind::LowVolume, start, end));
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn make_gap(source: &str, kind: GapKind, start: NaiveDateTime, end: NaiveDateTime) -> Gap {
+    return Gap {
+        source: source.to_owned(),
+        kind,
+        start,
+        end,
+        duration_secs: end.signed_duration_since(start).num_seconds(),
+    };
+}
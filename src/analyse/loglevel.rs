@@ -3,8 +3,10 @@
 use std::collections::HashMap;
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Emergency,
     Alert,
@@ -17,20 +19,52 @@ pub enum LogLevel {
     Others,
 }
 
+pub const LOGLEVELS: [LogLevel; 9] = [
+    LogLevel::Emergency,
+    LogLevel::Alert,
+    LogLevel::Critical,
+    LogLevel::Error,
+    LogLevel::Warning,
+    LogLevel::Notice,
+    LogLevel::Info,
+    LogLevel::Debug,
+    LogLevel::Others,
+];
+
+pub fn is_error_or_above(loglevel: &LogLevel) -> bool {
+    return *loglevel <= LogLevel::Error;
+}
+
+const LOGLEVEL_NAMES: [(&str, LogLevel); 15] = [
+    ("INFO", LogLevel::Info),
+    ("INFORMATION", LogLevel::Info),
+    ("DEBUG", LogLevel::Debug),
+    ("TRACE", LogLevel::Debug),
+    ("WARNING", LogLevel::Warning),
+    ("WARN", LogLevel::Warning),
+    ("ERROR", LogLevel::Error),
+    ("ERR", LogLevel::Error),
+    ("NOTICE", LogLevel::Notice),
+    ("CRITICAL", LogLevel::Critical),
+    ("CRIT", LogLevel::Critical),
+    ("FATAL", LogLevel::Critical),
+    ("ALERT", LogLevel::Alert),
+    ("EMERGENCY", LogLevel::Emergency),
+    ("EMERG", LogLevel::Emergency),
+];
+
 pub fn to_loglevel(loglevel: &str) -> LogLevel {
-    return match loglevel.to_uppercase().as_str() {
-        "INFO" => LogLevel::Info,
-        "INFORMATION" => LogLevel::Info,
-        "DEBUG" => LogLevel::Debug,
-        "WARNING" => LogLevel::Warning,
-        "WARN" => LogLevel::Warning,
-        "ERROR" => LogLevel::Error,
-        "NOTICE" => LogLevel::Notice,
-        "CRITICAL" => LogLevel::Critical,
-        "ALERT" => LogLevel::Alert,
-        "EMERGENCY" => LogLevel::Emergency,
-        _ => LogLevel::Others,
-    };
+    return bytes_to_loglevel(loglevel.as_bytes());
+}
+
+/// Loglevel of a name in any case, without allocating as it is looked up for
+/// every line
+pub fn bytes_to_loglevel(loglevel: &[u8]) -> LogLevel {
+    return LOGLEVEL_NAMES
+        .iter()
+        .find(|(name, _)| name.as_bytes().eq_ignore_ascii_case(loglevel))
+        .map(|(_, loglevel)| *loglevel)
+        .unwrap_or(LogLevel::Others);
 }
 
 pub fn make_loglevel_count_map() -> HashMap<LogLevel, i32> {
@@ -67,3 +101,11 @@ pub fn loglevel_regex_pattern() -> Regex {
     )
     .unwrap();
 }
+
+/// Regex finding any of the loglevel names `to_loglevel` knows, as a whole word
+/// so that eg: the `ERR` of `stderr` is not taken for a level. Stricter than
+/// [`loglevel_regex_pattern`], which the regex parser keeps using.
+pub fn loglevel_word_regex() -> Regex {
+    let names: Vec<&str> = LOGLEVEL_NAMES.iter().map(|(name, _)| *name).collect();
+    return Regex::new(&format!(r"(?i)\b({})\b", names.join("|"))).unwrap();
+}
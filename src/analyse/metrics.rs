@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::BTreeMap;
+
+use chrono::{Months, NaiveDateTime, TimeDelta};
+use serde::Serialize;
+
+use crate::analyse::{
+    dtfmt::DateTimeCat,
+    loglevel::{is_error_or_above, LogLevel, LOGLEVELS},
+    LogsAggregate,
+};
+
+const PERCENTILES: [u32; 4] = [50, 90, 95, 99];
+
+#[derive(Serialize)]
+pub struct LogMetrics {
+    /// Share of error and more severe logs among all the logs, from 0 to 1
+    pub error_ratio: f64,
+    pub errors_per_minute: f64,
+    pub errors_per_hour: f64,
+    pub peak_errors_per_minute: f64,
+    pub peak_errors_per_hour: f64,
+    /// Total number of logs in a bucket at the given percentiles
+    pub volume_percentiles: BTreeMap<String, i32>,
+    pub busiest_bucket: Option<BucketCount>,
+    pub quietest_bucket: Option<BucketCount>,
+    /// Running total of every loglevel at the end of each bucket
+    pub cumulative_counts: BTreeMap<LogLevel, Vec<i64>>,
+}
+
+#[derive(Serialize)]
+pub struct BucketCount {
+    pub datetime: NaiveDateTime,
+    pub count: i32,
+}
+
+pub fn compute_metrics(logs_aggregate: &LogsAggregate) -> LogMetrics {
+    let num_dt = logs_aggregate.datetimes.len();
+    let bucket_totals: Vec<i32> = (0..num_dt)
+        .map(|idx| {
+            LOGLEVELS
+                .iter()
+                .map(|loglevel| logs_aggregate.aggregates[loglevel][idx])
+                .sum()
+        })
+        .collect();
+    let bucket_errors: Vec<i32> = (0..num_dt)
+        .map(|idx| {
+            LOGLEVELS
+                .iter()
+                .filter(|loglevel| is_error_or_above(loglevel))
+                .map(|loglevel| logs_aggregate.aggregates[loglevel][idx])
+                .sum()
+        })
+        .collect();
+    let bucket_widths: Vec<f64> = (0..num_dt)
+        .map(|idx| bucket_width_secs(logs_aggregate, idx))
+        .collect();
+
+    let total_logs: i32 = bucket_totals.iter().sum();
+    let total_errors: i32 = bucket_errors.iter().sum();
+    let total_secs: f64 = bucket_widths.iter().sum();
+
+    let errors_per_second = if total_secs > 0.0 {
+        total_errors as f64 / total_secs
+    } else {
+        0.0
+    };
+    let peak_errors_per_second = bucket_errors
+        .iter()
+        .zip(&bucket_widths)
+        .map(|(errors, width)| *errors as f64 / width)
+        .fold(0.0, f64::max);
+
+    let mut sorted_totals = bucket_totals.to_vec();
+    sorted_totals.sort_unstable();
+    let volume_percentiles = PERCENTILES
+        .iter()
+        .filter_map(|percentile| {
+            let value = nearest_rank(&sorted_totals, *percentile)?;
+            Some((format!("p{}", percentile), value))
+        })
+        .collect();
+
+    let busiest_bucket = (0..num_dt)
+        .max_by_key(|idx| bucket_totals[*idx])
+        .map(|idx| BucketCount {
+            datetime: logs_aggregate.datetimes[idx],
+            count: bucket_totals[idx],
+        });
+    let quietest_bucket = (0..num_dt)
+        .min_by_key(|idx| bucket_totals[*idx])
+        .map(|idx| BucketCount {
+            datetime: logs_aggregate.datetimes[idx],
+            count: bucket_totals[idx],
+        });
+
+    let cumulative_counts = logs_aggregate
+        .aggregates
+        .iter()
+        .map(|(loglevel, counts)| {
+            let cumulative = counts
+                .iter()
+                .scan(0_i64, |total, count| {
+                    *total += *count as i64;
+                    Some(*total)
+                })
+                .collect();
+            (*loglevel, cumulative)
+        })
+        .collect();
+
+    return LogMetrics {
+        error_ratio: if total_logs > 0 {
+            total_errors as f64 / total_logs as f64
+        } else {
+            0.0
+        },
+        errors_per_minute: errors_per_second * 60.0,
+        errors_per_hour: errors_per_second * 3600.0,
+        peak_errors_per_minute: peak_errors_per_second * 60.0,
+        peak_errors_per_hour: peak_errors_per_second * 3600.0,
+        volume_percentiles,
+        busiest_bucket,
+        quietest_bucket,
+        cumulative_counts,
+    };
+}
+
+/// Length of the bucket in seconds, months and years are not of a fixed
+/// length so the length is taken from the calendar.
+pub fn bucket_width_secs(logs_aggregate: &LogsAggregate, idx: usize) -> f64 {
+    let start = logs_aggregate.datetimes[idx];
+    let end = bucket_end(logs_aggregate, idx);
+
+    return end.signed_duration_since(start).num_seconds() as f64;
+}
+
+/// Start of the bucket following the one at `idx`.
+pub fn bucket_end(logs_aggregate: &LogsAggregate, idx: usize) -> NaiveDateTime {
+    let start = logs_aggregate.datetimes[idx];
+    return match logs_aggregate.datetime_cat {
+        DateTimeCat::Seconds => start.checked_add_signed(TimeDelta::try_seconds(1).unwrap()),
+        DateTimeCat::Minutes => start.checked_add_signed(TimeDelta::try_minutes(1).unwrap()),
+        DateTimeCat::Hours => start.checked_add_signed(TimeDelta::try_hours(1).unwrap()),
+        DateTimeCat::Days => start.checked_add_signed(TimeDelta::try_days(1).unwrap()),
+        DateTimeCat::Months => start.checked_add_months(Months::new(1)),
+        DateTimeCat::Years => start.checked_add_months(Months::new(12)),
+    }
+    .unwrap();
+}
+
+fn nearest_rank(sorted_values: &[i32], percentile: u32) -> Option<i32> {
+    if sorted_values.is_empty() {
+        return None;
+    }
+    let rank = (percentile as usize * sorted_values.len()).div_ceil(100);
+
+    return Some(sorted_values[rank.max(1) - 1]);
+}
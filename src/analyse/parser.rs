@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{borrow::Cow, collections::BTreeMap, fmt};
+
+use chrono::{DateTime, NaiveDateTime};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::analyse::loglevel::{to_loglevel, LogLevel};
+
+mod access;
+mod json;
+mod logfmt;
+mod plain;
+mod syslog;
+
+pub use self::access::AccessLogParser;
+pub use self::json::JsonParser;
+pub use self::logfmt::LogfmtParser;
+pub use self::plain::RegexParser;
+pub use self::syslog::SyslogParser;
+
+/// What a parser found in a log line. The message is borrowed from the line
+/// when it is in it as is, so that most lines are parsed without copying it.
+pub struct LogRecord<'a> {
+    pub datetime: NaiveDateTime,
+    pub loglevel: LogLevel,
+    pub message: Cow<'a, str>,
+    /// Other fields of the record, eg: the host of a syslog record or the
+    /// status of an access log. Empty for plain text logs.
+    pub fields: BTreeMap<String, String>,
+}
+
+impl LogRecord<'_> {
+    /// Same record, holding a copy of its message instead of borrowing it
+    /// from the line.
+    pub fn into_owned(self) -> LogRecord<'static> {
+        return LogRecord {
+            datetime: self.datetime,
+            loglevel: self.loglevel,
+            message: Cow::Owned(self.message.into_owned()),
+            fields: self.fields,
+        };
+    }
+}
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineParseError {
+    NoDatetimeMatch,
+    DatetimeParseError,
+    /// The line is not a record of the parser's format, eg: not a JSON object
+    MalformedRecord,
+}
+
+impl fmt::Display for LineParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.write_str(match self {
+            LineParseError::NoDatetimeMatch => "no datetime match",
+            LineParseError::DatetimeParseError => "datetime parse error",
+            LineParseError::MalformedRecord => "malformed record",
+        });
+    }
+}
+
+/// Turns a log line into a [`LogRecord`]. Lines are parsed on several
+/// threads at once, so a parser is shared between them.
+pub trait LogParser: Sync {
+    fn parse<'a>(&self, line: &'a str) -> Result<LogRecord<'a>, LineParseError>;
+
+    /// Parses a line as read from a file, which may not be valid UTF-8. The
+    /// invalid bytes are replaced with U+FFFD before parsing unless the
+    /// parser works on bytes itself.
+    fn parse_bytes<'a>(&self, line: &'a [u8]) -> Result<LogRecord<'a>, LineParseError> {
+        return match String::from_utf8_lossy(line) {
+            Cow::Borrowed(line) => self.parse(line),
+            Cow::Owned(line) => self.parse(&line).map(LogRecord::into_owned),
+        };
+    }
+
+    /// Parser for the lines of a file last written at `modified`, for
+    /// formats whose timestamps leave out part of the date. None when the
+    /// lines of every file are parsed the same.
+    fn for_source(&self, _modified: NaiveDateTime) -> Option<Box<dyn LogParser>> {
+        return None;
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParserKind {
+    /// Finds the datetime with the datetime format and the first loglevel name in the line
+    #[default]
+    Regex,
+    /// One JSON object per line
+    Json,
+    /// Space separated key=value pairs
+    Logfmt,
+    /// RFC 3164 and RFC 5424 syslog lines
+    Syslog,
+    /// Common and combined access logs of web servers
+    Access,
+}
+
+impl ParserKind {
+    /// Parser of this kind, `datetime_format` is used by the regex parser,
+    /// and by the json and logfmt parsers when it is not empty. `syslog_year`
+    /// is the year of the syslog timestamps without one, inferred from the
+    /// files when not given.
+    pub fn make_parser(
+        &self,
+        datetime_format: &str,
+        syslog_year: Option<i32>,
+    ) -> Box<dyn LogParser> {
+        return match self {
+            ParserKind::Regex => Box::new(RegexParser::new(datetime_format)),
+            ParserKind::Json => Box::new(JsonParser::new(datetime_format)),
+            ParserKind::Logfmt => Box::new(LogfmtParser::new(datetime_format)),
+            ParserKind::Syslog => match syslog_year {
+                Some(year) => Box::new(SyslogParser::new().year(year)),
+                None => Box::new(SyslogParser::new()),
+            },
+            ParserKind::Access => Box::new(AccessLogParser::new()),
+        };
+    }
+
+    /// Structured records carry their own timestamps, only the regex parser
+    /// has to be told what a datetime looks like.
+    pub fn needs_datetime_format(&self) -> bool {
+        return *self == ParserKind::Regex;
+    }
+}
+
+const TIME_KEYS: [&str; 7] = [
+    "timestamp",
+    "@timestamp",
+    "time",
+    "ts",
+    "datetime",
+    "date",
+    "t",
+];
+const LEVEL_KEYS: [&str; 6] = [
+    "level",
+    "log.level",
+    "severity",
+    "lvl",
+    "loglevel",
+    "levelname",
+];
+const MESSAGE_KEYS: [&str; 4] = ["message", "msg", "@message", "event"];
+
+/// Makes a record of the fields of a json or logfmt line, the timestamp,
+/// level and message are looked up under their usual keys.
+fn record_from_fields(
+    mut fields: BTreeMap<String, String>,
+    datetime_format: &str,
+) -> Result<LogRecord<'static>, LineParseError> {
+    let mut take = |keys: &[&str]| keys.iter().find_map(|key| fields.remove(*key));
+
+    let timestamp = take(&TIME_KEYS).ok_or(LineParseError::NoDatetimeMatch)?;
+    let datetime = parse_timestamp(&timestamp, datetime_format)?;
+    let loglevel = match take(&LEVEL_KEYS) {
+        Some(level) => match level.parse::<u32>() {
+            Ok(level) => numeric_loglevel(level),
+            Err(_) => to_loglevel(&level),
+        },
+        None => LogLevel::Others,
+    };
+    let message = take(&MESSAGE_KEYS).unwrap_or_default();
+
+    return Ok(LogRecord {
+        datetime,
+        loglevel,
+        message: Cow::Owned(message),
+        fields,
+    });
+}
+
+/// Numeric levels of bunyan and pino, from 10 for trace to 60 for fatal
+fn numeric_loglevel(level: u32) -> LogLevel {
+    return match level {
+        0..=29 => LogLevel::Debug,
+        30..=39 => LogLevel::Info,
+        40..=49 => LogLevel::Warning,
+        50..=59 => LogLevel::Error,
+        _ => LogLevel::Critical,
+    };
+}
+
+/// Parses a timestamp with `datetime_format` when it is given. Otherwise as
+/// RFC 3339, as an ISO 8601 datetime without offset, or as seconds or
+/// milliseconds since the epoch. Timestamps with an offset keep the time
+/// written in them, like the datetimes found by the regex parser.
+fn parse_timestamp(
+    timestamp: &str,
+    datetime_format: &str,
+) -> Result<NaiveDateTime, LineParseError> {
+    let timestamp = timestamp.trim();
+    if !datetime_format.is_empty() {
+        return NaiveDateTime::parse_from_str(timestamp, datetime_format)
+            .map_err(|_| LineParseError::DatetimeParseError);
+    }
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(timestamp) {
+        return Ok(datetime.naive_local());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(timestamp, format) {
+            return Ok(datetime);
+        }
+    }
+    if let Ok(epoch) = timestamp.parse::<f64>() {
+        // Anything past the year 5138 in seconds is taken as milliseconds
+        let epoch_millis = if epoch.abs() < 1e11 {
+            epoch * 1000.0
+        } else {
+            epoch
+        };
+        return DateTime::from_timestamp_millis(epoch_millis as i64)
+            .map(|datetime| datetime.naive_utc())
+            .ok_or(LineParseError::DatetimeParseError);
+    }
+
+    return Err(LineParseError::DatetimeParseError);
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use chrono::DateTime;
+use regex::Regex;
+
+use super::{LineParseError, LogParser, LogRecord};
+use crate::analyse::loglevel::LogLevel;
+
+const ACCESS_DATETIME_FMT: &str = "%d/%b/%Y:%H:%M:%S %z";
+
+/// Parses the common and combined log formats of Apache and nginx access
+/// logs. Server errors are logged as errors, client errors as warnings and
+/// every other response as info, the request line is the message.
+pub struct AccessLogParser {
+    access_regex: Regex,
+}
+
+impl AccessLogParser {
+    pub fn new() -> AccessLogParser {
+        return AccessLogParser {
+            access_regex: Regex::new(
+                r#"^(?<remote_addr>\S+) \S+ (?<remote_user>\S+) \[(?<ts>[^\]]+)\] "(?<request>(?:[^"\\]|\\.)*)" (?<status>\d{3}) (?<bytes>\d+|-)(?: "(?<referer>(?:[^"\\]|\\.)*)" "(?<user_agent>(?:[^"\\]|\\.)*)")?"#,
+            )
+            .unwrap(),
+        };
+    }
+}
+
+impl Default for AccessLogParser {
+    fn default() -> Self {
+        return AccessLogParser::new();
+    }
+}
+
+impl LogParser for AccessLogParser {
+    fn parse<'a>(&self, line: &'a str) -> Result<LogRecord<'a>, LineParseError> {
+        let caps = self
+            .access_regex
+            .captures(line)
+            .ok_or(LineParseError::MalformedRecord)?;
+
+        let datetime = DateTime::parse_from_str(&caps["ts"], ACCESS_DATETIME_FMT)
+            .map_err(|_| LineParseError::DatetimeParseError)?
+            .naive_local();
+
+        let mut fields: BTreeMap<String, String> = BTreeMap::new();
+        for name in [
+            "remote_addr",
+            "remote_user",
+            "status",
+            "bytes",
+            "referer",
+            "user_agent",
+        ] {
+            if let Some(value) = caps.name(name).filter(|value| value.as_str() != "-") {
+                fields.insert(name.to_owned(), value.as_str().to_owned());
+            }
+        }
+        let request = caps.name("request").map_or("", |request| request.as_str());
+        let mut request_parts = request.splitn(3, ' ');
+        for name in ["method", "path", "protocol"] {
+            if let Some(part) = request_parts.next().filter(|part| !part.is_empty()) {
+                fields.insert(name.to_owned(), part.to_owned());
+            }
+        }
+
+        let loglevel = match caps["status"].as_bytes()[0] {
+            b'5' => LogLevel::Error,
+            b'4' => LogLevel::Warning,
+            _ => LogLevel::Info,
+        };
+
+        return Ok(LogRecord {
+            datetime,
+            loglevel,
+            message: Cow::Borrowed(request),
+            fields,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    #[test]
+    fn parses_common_log_format() {
+        let parser = AccessLogParser::new();
+        let record = parser
+            .parse(r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326"#)
+            .unwrap();
+
+        // The time written in the line is kept, like the other parsers do
+        assert_eq!(
+            record.datetime,
+            NaiveDateTime::parse_from_str("2000-10-10 13:55:36", "%Y-%m-%d %H:%M:%S").unwrap()
+        );
+        assert_eq!(record.loglevel, LogLevel::Info);
+        assert_eq!(record.message, "GET /apache_pb.gif HTTP/1.0");
+        assert_eq!(record.fields["remote_addr"], "127.0.0.1");
+        assert_eq!(record.fields["remote_user"], "frank");
+        assert_eq!(record.fields["method"], "GET");
+        assert_eq!(record.fields["path"], "/apache_pb.gif");
+        assert_eq!(record.fields["status"], "200");
+        assert_eq!(record.fields["bytes"], "2326");
+        assert!(!record.fields.contains_key("referer"));
+    }
+
+    #[test]
+    fn gives_the_loglevel_of_the_status() {
+        let parser = AccessLogParser::new();
+        let record = parser
+            .parse(r#"10.0.0.7 - - [01/Mar/2024:10:00:00 +0000] "POST /api/orders HTTP/1.1" 503 - "https://shop.example/cart" "Mozilla/5.0 (X11; Linux x86_64)""#)
+            .unwrap();
+        assert_eq!(record.loglevel, LogLevel::Error);
+        assert_eq!(record.fields["status"], "503");
+        assert_eq!(record.fields["referer"], "https://shop.example/cart");
+        assert_eq!(
+            record.fields["user_agent"],
+            "Mozilla/5.0 (X11; Linux x86_64)"
+        );
+        assert!(!record.fields.contains_key("remote_user"));
+        assert!(!record.fields.contains_key("bytes"));
+
+        let record = parser
+            .parse(r#"10.0.0.7 - - [01/Mar/2024:10:00:01 +0000] "GET /missing HTTP/1.1" 404 153"#)
+            .unwrap();
+        assert_eq!(record.loglevel, LogLevel::Warning);
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        let parser = AccessLogParser::new();
+        assert_eq!(
+            parser.parse("2024-03-01 10:00:00 INFO started").err(),
+            Some(LineParseError::MalformedRecord)
+        );
+        assert_eq!(
+            parser
+                .parse(r#"10.0.0.7 - - [2024-03-01 10:00:00] "GET / HTTP/1.1" 200 12"#)
+                .err(),
+            Some(LineParseError::DatetimeParseError)
+        );
+    }
+}
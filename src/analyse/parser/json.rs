@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use super::{record_from_fields, LineParseError, LogParser, LogRecord};
+
+/// Parses lines holding one JSON object each. Nested objects are flattened
+/// into dotted keys, eg: `{"log": {"level": "info"}}` gives `log.level`.
+pub struct JsonParser {
+    datetime_format: String,
+}
+
+impl JsonParser {
+    pub fn new(datetime_format: &str) -> JsonParser {
+        return JsonParser {
+            datetime_format: datetime_format.to_owned(),
+        };
+    }
+}
+
+impl LogParser for JsonParser {
+    fn parse<'a>(&self, line: &'a str) -> Result<LogRecord<'a>, LineParseError> {
+        let object: Map<String, Value> =
+            serde_json::from_str(line).map_err(|_| LineParseError::MalformedRecord)?;
+
+        let mut fields: BTreeMap<String, String> = BTreeMap::new();
+        flatten_object("", object, &mut fields);
+
+        return record_from_fields(fields, &self.datetime_format);
+    }
+}
+
+fn flatten_object(prefix: &str, object: Map<String, Value>, fields: &mut BTreeMap<String, String>) {
+    for (key, value) in object {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Object(object) => flatten_object(&key, object, fields),
+            Value::String(text) => {
+                fields.insert(key, text);
+            }
+            Value::Null => {
+                fields.insert(key, String::new());
+            }
+            value => {
+                fields.insert(key, value.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::analyse::loglevel::LogLevel;
+
+    fn datetime(text: &str) -> NaiveDateTime {
+        return NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f").unwrap();
+    }
+
+    #[test]
+    fn parses_epoch_timestamps() {
+        let parser = JsonParser::new("");
+
+        let record = parser
+            .parse(r#"{"time": 1709287200, "level": "info", "msg": "in seconds"}"#)
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2024-03-01 10:00:00"));
+
+        let record = parser
+            .parse(r#"{"time": 1709287200.5, "level": "info", "msg": "with a fraction"}"#)
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2024-03-01 10:00:00.5"));
+
+        let record = parser
+            .parse(r#"{"ts": 1709287200123, "level": "info", "msg": "in milliseconds"}"#)
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2024-03-01 10:00:00.123"));
+
+        let record = parser
+            .parse(r#"{"@timestamp": "2024-03-01T10:00:00+02:00", "msg": "with an offset"}"#)
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2024-03-01 10:00:00"));
+        assert_eq!(record.loglevel, LogLevel::Others);
+    }
+
+    #[test]
+    fn parses_numeric_levels() {
+        let parser = JsonParser::new("");
+        for (level, loglevel) in [
+            (10, LogLevel::Debug),
+            (20, LogLevel::Debug),
+            (30, LogLevel::Info),
+            (40, LogLevel::Warning),
+            (50, LogLevel::Error),
+            (60, LogLevel::Critical),
+        ] {
+            let line = format!(
+                r#"{{"time": 1709287200000, "level": {}, "msg": "x"}}"#,
+                level
+            );
+            assert_eq!(parser.parse(&line).unwrap().loglevel, loglevel, "{}", level);
+        }
+        let record = parser
+            .parse(r#"{"time": 1709287200, "severity": "WARN", "msg": "x"}"#)
+            .unwrap();
+        assert_eq!(record.loglevel, LogLevel::Warning);
+    }
+
+    #[test]
+    fn flattens_nested_objects() {
+        let parser = JsonParser::new("");
+        let record = parser
+            .parse(r#"{"time": 1709287200, "log": {"level": "error"}, "message": "boom", "req": {"id": 7, "user": null}}"#)
+            .unwrap();
+        assert_eq!(record.loglevel, LogLevel::Error);
+        assert_eq!(record.message, "boom");
+        assert_eq!(record.fields["req.id"], "7");
+        assert_eq!(record.fields["req.user"], "");
+        assert_eq!(record.fields.len(), 2);
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        let parser = JsonParser::new("");
+        assert_eq!(
+            parser.parse("[1, 2, 3]").err(),
+            Some(LineParseError::MalformedRecord)
+        );
+        assert_eq!(
+            parser.parse(r#"{"level": "info", "msg": "no time"}"#).err(),
+            Some(LineParseError::NoDatetimeMatch)
+        );
+        assert_eq!(
+            parser.parse(r#"{"time": "yesterday", "msg": "x"}"#).err(),
+            Some(LineParseError::DatetimeParseError)
+        );
+    }
+}
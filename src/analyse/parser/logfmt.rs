@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::BTreeMap;
+
+use super::{record_from_fields, LineParseError, LogParser, LogRecord};
+
+/// Parses logfmt lines like `ts=2024-03-01T10:00:00Z level=info msg="started"`,
+/// a key without a value is kept with an empty value.
+pub struct LogfmtParser {
+    datetime_format: String,
+}
+
+impl LogfmtParser {
+    pub fn new(datetime_format: &str) -> LogfmtParser {
+        return LogfmtParser {
+            datetime_format: datetime_format.to_owned(),
+        };
+    }
+}
+
+impl LogParser for LogfmtParser {
+    fn parse<'a>(&self, line: &'a str) -> Result<LogRecord<'a>, LineParseError> {
+        if !line.contains('=') {
+            return Err(LineParseError::MalformedRecord);
+        }
+
+        return record_from_fields(parse_pairs(line)?, &self.datetime_format);
+    }
+}
+
+fn parse_pairs(line: &str) -> Result<BTreeMap<String, String>, LineParseError> {
+    let mut fields: BTreeMap<String, String> = BTreeMap::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && !c.is_whitespace()) {
+            key.push(c);
+        }
+        if key.is_empty() {
+            return Err(LineParseError::MalformedRecord);
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            if chars.next_if_eq(&'"').is_some() {
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(c) => value.push(c),
+                            None => return Err(LineParseError::MalformedRecord),
+                        },
+                        Some(c) => value.push(c),
+                        None => return Err(LineParseError::MalformedRecord),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    value.push(c);
+                }
+            }
+        }
+        fields.insert(key, value);
+    }
+
+    return Ok(fields);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::analyse::loglevel::LogLevel;
+
+    #[test]
+    fn parses_quoted_and_escaped_values() {
+        let parser = LogfmtParser::new("");
+        let record = parser
+            .parse(r#"ts=2024-03-01T10:00:00Z level=warn msg="disk \"sda\" at 90%\nretrying\tsoon" host=web-1 dry_run"#)
+            .unwrap();
+
+        assert_eq!(
+            record.datetime,
+            NaiveDateTime::parse_from_str("2024-03-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+        );
+        assert_eq!(record.loglevel, LogLevel::Warning);
+        assert_eq!(record.message, "disk \"sda\" at 90%\nretrying\tsoon");
+        assert_eq!(record.fields["host"], "web-1");
+        assert_eq!(record.fields["dry_run"], "");
+        assert_eq!(record.fields.len(), 2);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let parser = LogfmtParser::new("");
+        for line in [
+            "no pairs in this line",
+            r#"ts=2024-03-01T10:00:00Z msg="never closed"#,
+            r#"ts=2024-03-01T10:00:00Z msg="ends in an escape\"#,
+            "ts=2024-03-01T10:00:00Z =value",
+        ] {
+            assert_eq!(
+                parser.parse(line).err(),
+                Some(LineParseError::MalformedRecord),
+                "{}",
+                line
+            );
+        }
+        assert_eq!(
+            parser.parse("level=info msg=started").err(),
+            Some(LineParseError::NoDatetimeMatch)
+        );
+    }
+
+    #[test]
+    fn uses_the_datetime_format_when_given() {
+        let parser = LogfmtParser::new("%d/%m/%Y %H:%M");
+        let record = parser
+            .parse(r#"time="01/03/2024 10:30" level=info msg=ok"#)
+            .unwrap();
+        assert_eq!(
+            record.datetime,
+            NaiveDateTime::parse_from_str("2024-03-01 10:30:00", "%Y-%m-%d %H:%M:%S").unwrap()
+        );
+        assert_eq!(
+            parser.parse("time=2024-03-01T10:30:00Z msg=ok").err(),
+            Some(LineParseError::DatetimeParseError)
+        );
+    }
+}
@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use chrono::NaiveDateTime;
+use regex::bytes::Regex;
+
+use super::{LineParseError, LogParser, LogRecord};
+use crate::analyse::{
+    dtfmt::dt_fmt_to_regex,
+    loglevel::{bytes_to_loglevel, loglevel_regex_pattern, LogLevel},
+};
+
+/// Finds the datetime of a plain text line with the datetime format and
+/// takes the first loglevel name in the line as its loglevel. The line is
+/// searched as bytes and the message is borrowed from it.
+pub struct RegexParser {
+    datetime_format: String,
+    dt_regex: Regex,
+    ll_regex: Regex,
+}
+
+impl RegexParser {
+    pub fn new(datetime_format: &str) -> RegexParser {
+        return RegexParser {
+            datetime_format: datetime_format.to_owned(),
+            dt_regex: Regex::new(dt_fmt_to_regex(datetime_format).as_str()).unwrap(),
+            ll_regex: Regex::new(loglevel_regex_pattern().as_str()).unwrap(),
+        };
+    }
+}
+
+impl LogParser for RegexParser {
+    fn parse<'a>(&self, log: &'a str) -> Result<LogRecord<'a>, LineParseError> {
+        return self.parse_bytes(log.as_bytes());
+    }
+
+    fn parse_bytes<'a>(&self, log: &'a [u8]) -> Result<LogRecord<'a>, LineParseError> {
+        let datetime_match = match self.dt_regex.find(log) {
+            Some(datetime_match) => datetime_match,
+            _ => return Err(LineParseError::NoDatetimeMatch),
+        };
+
+        let datetime = std::str::from_utf8(datetime_match.as_bytes())
+            .ok()
+            .and_then(|datetime| {
+                NaiveDateTime::parse_from_str(datetime, &self.datetime_format).ok()
+            })
+            .ok_or(LineParseError::DatetimeParseError)?;
+
+        // The message is what follows the datetime and the loglevel
+        let (loglevel, message_start) = match self.ll_regex.find(log) {
+            Some(ll_match) => (
+                bytes_to_loglevel(ll_match.as_bytes()),
+                ll_match.end().max(datetime_match.end()),
+            ),
+            _ => (LogLevel::Others, datetime_match.end()),
+        };
+        // Only a message with invalid UTF-8 is copied, to replace the bytes
+        let message = match String::from_utf8_lossy(&log[message_start..]) {
+            Cow::Borrowed(message) => Cow::Borrowed(trim_separators(message)),
+            Cow::Owned(message) => Cow::Owned(trim_separators(&message).to_owned()),
+        };
+
+        return Ok(LogRecord {
+            datetime,
+            loglevel,
+            message,
+            fields: BTreeMap::new(),
+        });
+    }
+}
+
+// Separators between the datetime or the loglevel and the message
+fn trim_separators(message: &str) -> &str {
+    return message.trim_start_matches(|c: char| c.is_whitespace() || "-:|".contains(c));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_first_loglevel_name_anywhere_in_the_line() {
+        let parser = RegexParser::new("%Y-%m-%d %H:%M:%S");
+        for (line, loglevel) in [
+            ("2024-03-01 10:00:00 [INFO] started", LogLevel::Info),
+            (
+                "2024-03-01 10:00:00 Errors occurred in worker",
+                LogLevel::Error,
+            ),
+            ("2024-03-01 10:00:00 app.ERROR_CODE=5", LogLevel::Error),
+            ("2024-03-01 10:00:00 WARNINGS: 3", LogLevel::Warning),
+            ("2024-03-01 10:00:00 information only", LogLevel::Info),
+            ("2024-03-01 10:00:00 FATAL boom", LogLevel::Others),
+        ] {
+            assert_eq!(parser.parse(line).unwrap().loglevel, loglevel, "{}", line);
+        }
+    }
+}
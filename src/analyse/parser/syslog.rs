@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use chrono::{Datelike, Local, Months, NaiveDateTime, TimeDelta};
+use regex::{Captures, Regex};
+
+use super::{parse_timestamp, LineParseError, LogParser, LogRecord};
+use crate::analyse::loglevel::{loglevel_word_regex, to_loglevel, LogLevel};
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+/// Severities of the syslog priority, from 0 to 7
+const SEVERITIES: [LogLevel; 8] = [
+    LogLevel::Emergency,
+    LogLevel::Alert,
+    LogLevel::Critical,
+    LogLevel::Error,
+    LogLevel::Warning,
+    LogLevel::Notice,
+    LogLevel::Info,
+    LogLevel::Debug,
+];
+
+/// Parses RFC 5424 lines, and RFC 3164 lines with or without the priority
+/// like the ones in /var/log/syslog. The loglevel comes from the priority,
+/// lines without one get the first loglevel name in their message.
+#[derive(Clone)]
+pub struct SyslogParser {
+    rfc5424_regex: Regex,
+    rfc3164_regex: Regex,
+    ll_regex: Regex,
+    /// Year of the RFC 3164 timestamps, inferred when not given
+    year: Option<i32>,
+    /// Last write to the file the lines are read from, the current time when
+    /// it is not known
+    modified: Option<NaiveDateTime>,
+}
+
+impl SyslogParser {
+    pub fn new() -> SyslogParser {
+        return SyslogParser {
+            rfc5424_regex: Regex::new(
+                r"^<(?<pri>\d{1,3})>\d{1,2} (?<ts>\S+) (?<host>\S+) (?<app>\S+) (?<pid>\S+) (?<msgid>\S+) (?<sd>-|(?:\[(?:[^\]\\]|\\.)*\])+) ?(?<msg>.*)$",
+            )
+            .unwrap(),
+            rfc3164_regex: Regex::new(
+                r"^(?:<(?<pri>\d{1,3})>)?(?<ts>[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}|\d{4}-\d{2}-\d{2}T\S+) (?<host>\S+) (?:(?<app>[^\s:\[]+)(?:\[(?<pid>[^\]]*)\])?: )?(?<msg>.*)$",
+            )
+            .unwrap(),
+            ll_regex: loglevel_word_regex(),
+            year: None,
+            modified: None,
+        };
+    }
+
+    /// Takes the RFC 3164 timestamps to be of `year` instead of inferring it.
+    pub fn year(mut self, year: i32) -> SyslogParser {
+        self.year = Some(year);
+        return self;
+    }
+
+    fn make_record<'a>(
+        &self,
+        caps: &Captures<'a>,
+        datetime: NaiveDateTime,
+    ) -> Result<LogRecord<'a>, LineParseError> {
+        let message = caps.name("msg").map_or("", |msg| msg.as_str());
+        let mut fields: BTreeMap<String, String> = BTreeMap::new();
+        let mut add_field = |name: &str, group: &str| {
+            if let Some(value) = caps.name(group).filter(|value| value.as_str() != "-") {
+                fields.insert(name.to_owned(), value.as_str().to_owned());
+            }
+        };
+        add_field("host", "host");
+        add_field("app", "app");
+        add_field("pid", "pid");
+        add_field("msgid", "msgid");
+        add_field("structured_data", "sd");
+
+        let loglevel = match caps.name("pri") {
+            Some(pri) => {
+                let pri: usize = pri
+                    .as_str()
+                    .parse()
+                    .map_err(|_| LineParseError::MalformedRecord)?;
+                let facility = FACILITIES
+                    .get(pri / 8)
+                    .ok_or(LineParseError::MalformedRecord)?;
+                fields.insert("facility".to_owned(), facility.to_string());
+                SEVERITIES[pri % 8]
+            }
+            None => match self.ll_regex.find(message) {
+                Some(ll_match) => to_loglevel(ll_match.as_str()),
+                None => LogLevel::Others,
+            },
+        };
+
+        return Ok(LogRecord {
+            datetime,
+            loglevel,
+            message: Cow::Borrowed(message),
+            fields,
+        });
+    }
+}
+
+impl Default for SyslogParser {
+    fn default() -> Self {
+        return SyslogParser::new();
+    }
+}
+
+impl LogParser for SyslogParser {
+    fn parse<'a>(&self, line: &'a str) -> Result<LogRecord<'a>, LineParseError> {
+        if let Some(caps) = self.rfc5424_regex.captures(line) {
+            let datetime = match &caps["ts"] {
+                "-" => return Err(LineParseError::NoDatetimeMatch),
+                timestamp => parse_timestamp(timestamp, "")?,
+            };
+            return self.make_record(&caps, datetime);
+        }
+
+        let caps = self
+            .rfc3164_regex
+            .captures(line)
+            .ok_or(LineParseError::MalformedRecord)?;
+        let timestamp = &caps["ts"];
+        let datetime = if timestamp.starts_with(|c: char| c.is_ascii_digit()) {
+            parse_timestamp(timestamp, "")?
+        } else {
+            self.parse_bsd_timestamp(timestamp)?
+        };
+        return self.make_record(&caps, datetime);
+    }
+
+    fn for_source(&self, modified: NaiveDateTime) -> Option<Box<dyn LogParser>> {
+        if self.year.is_some() {
+            return None;
+        }
+        return Some(Box::new(SyslogParser {
+            modified: Some(modified),
+            ..self.clone()
+        }));
+    }
+}
+
+impl SyslogParser {
+    /// RFC 3164 timestamps have no year. Unless it is given, the year of the
+    /// last write to the file is assumed, or the year before when that puts
+    /// the log after the write, eg: the December logs of a file rotated in
+    /// January.
+    fn parse_bsd_timestamp(&self, timestamp: &str) -> Result<NaiveDateTime, LineParseError> {
+        let modified = self.modified.unwrap_or_else(|| Local::now().naive_local());
+        let datetime = NaiveDateTime::parse_from_str(
+            &format!("{} {}", self.year.unwrap_or(modified.year()), timestamp),
+            "%Y %b %e %H:%M:%S",
+        )
+        .map_err(|_| LineParseError::DatetimeParseError)?;
+
+        if self.year.is_none() && datetime > modified + TimeDelta::days(1) {
+            return datetime
+                .checked_sub_months(Months::new(12))
+                .ok_or(LineParseError::DatetimeParseError);
+        }
+        return Ok(datetime);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(text: &str) -> NaiveDateTime {
+        return NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap();
+    }
+
+    #[test]
+    fn parses_rfc3164_lines() {
+        let parser = SyslogParser::new().year(2024);
+
+        let record = parser
+            .parse("<34>Oct 11 22:14:15 mymachine su: 'su root' failed for lonvick")
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2024-10-11 22:14:15"));
+        assert_eq!(record.loglevel, LogLevel::Critical);
+        assert_eq!(record.message, "'su root' failed for lonvick");
+        assert_eq!(record.fields["facility"], "auth");
+        assert_eq!(record.fields["host"], "mymachine");
+        assert_eq!(record.fields["app"], "su");
+
+        // Days before the 10th are padded with a space
+        let record = parser
+            .parse("Mar  1 09:05:03 web-1 CRON[4242]: (root) CMD (run-parts /etc/cron.hourly)")
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2024-03-01 09:05:03"));
+        assert_eq!(record.loglevel, LogLevel::Others);
+        assert_eq!(record.message, "(root) CMD (run-parts /etc/cron.hourly)");
+        assert_eq!(record.fields["app"], "CRON");
+        assert_eq!(record.fields["pid"], "4242");
+        assert!(!record.fields.contains_key("facility"));
+
+        let record = parser
+            .parse("Mar 12 09:05:03 web-1 kernel: ERROR: disk sda is full")
+            .unwrap();
+        assert_eq!(record.loglevel, LogLevel::Error);
+    }
+
+    #[test]
+    fn parses_rfc5424_lines() {
+        let parser = SyslogParser::new();
+
+        let record = parser
+            .parse(r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="Application"] An application event"#)
+            .unwrap();
+        assert_eq!(
+            record.datetime,
+            NaiveDateTime::parse_from_str("2003-10-11 22:14:15.003", "%Y-%m-%d %H:%M:%S%.f")
+                .unwrap()
+        );
+        assert_eq!(record.loglevel, LogLevel::Notice);
+        assert_eq!(record.message, "An application event");
+        assert_eq!(record.fields["facility"], "local4");
+        assert_eq!(record.fields["app"], "evntslog");
+        assert_eq!(record.fields["msgid"], "ID47");
+        assert!(!record.fields.contains_key("pid"));
+
+        assert_eq!(
+            parser.parse("<13>1 - host app - - - no timestamp").err(),
+            Some(LineParseError::NoDatetimeMatch)
+        );
+        assert_eq!(
+            parser
+                .parse("<999>Oct 11 22:14:15 host app: bad priority")
+                .err(),
+            Some(LineParseError::MalformedRecord)
+        );
+        assert_eq!(
+            parser.parse("not a syslog line").err(),
+            Some(LineParseError::MalformedRecord)
+        );
+    }
+
+    #[test]
+    fn infers_the_year_from_the_last_write() {
+        let parser = SyslogParser::new()
+            .for_source(datetime("2021-01-01 10:00:00"))
+            .unwrap();
+
+        let record = parser
+            .parse("Dec 31 23:59:00 host app: last of the year")
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2020-12-31 23:59:00"));
+        let record = parser
+            .parse("Jan  1 00:00:01 host app: first of the year")
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2021-01-01 00:00:01"));
+
+        // A given year is kept whatever the last write
+        let parser = SyslogParser::new().year(2019);
+        assert!(parser.for_source(datetime("2021-01-01 10:00:00")).is_none());
+        let record = parser
+            .parse("Dec 31 23:59:00 host app: given year")
+            .unwrap();
+        assert_eq!(record.datetime, datetime("2019-12-31 23:59:00"));
+    }
+}
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::analyse::loglevel::LogLevel;
+
+/// Number of the most frequent message patterns kept in the analysis
+pub const TOP_PATTERNS: usize = 10;
+const MAX_PATTERN_LEN: usize = 200;
+/// Most patterns counted at once. Past twice as many the least frequent ones
+/// are dropped, so with messages of that many patterns the counts of the top
+/// ones are a lower bound.
+const MAX_COUNTED_PATTERNS: usize = 10_000;
+
+/// Messages of a loglevel that only differ in their numbers, ids and
+/// addresses, eg: `connection to <ip> timed out after <n>ms`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct MessagePattern {
+    pub loglevel: LogLevel,
+    pub pattern: String,
+    pub count: i32,
+    /// First message found with this pattern
+    pub example: String,
+}
+
+pub struct PatternCounter {
+    uuid_regex: Regex,
+    ip_regex: Regex,
+    hex_regex: Regex,
+    number_regex: Regex,
+    counts: HashMap<(LogLevel, String), (i32, String)>,
+}
+
+impl PatternCounter {
+    pub fn new() -> PatternCounter {
+        return PatternCounter {
+            uuid_regex: Regex::new(
+                r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+            )
+            .unwrap(),
+            ip_regex: Regex::new(r"\b\d{1,3}(?:\.\d{1,3}){3}(?::\d+)?\b").unwrap(),
+            hex_regex: Regex::new(r"(?i)\b0x[0-9a-f]+\b|\b[0-9a-f]{6,}\b").unwrap(),
+            number_regex: Regex::new(r"\d+(?:[.,]\d+)*").unwrap(),
+            counts: HashMap::new(),
+        };
+    }
+
+    pub fn add(&mut self, loglevel: LogLevel, message: &str) {
+        let message = message.trim();
+        // The uuids and addresses go first so that their digits are not
+        // replaced one by one
+        let mut pattern = self.uuid_regex.replace_all(message, "<uuid>").into_owned();
+        pattern = self.ip_regex.replace_all(&pattern, "<ip>").into_owned();
+        pattern = self
+            .hex_regex
+            .replace_all(&pattern, |caps: &Captures| {
+                let word = &caps[0];
+                // Words like "decade" are made of hex digits too
+                let is_hex = word[..2].eq_ignore_ascii_case("0x")
+                    || (word.bytes().any(|b| b.is_ascii_digit())
+                        && word.bytes().any(|b| b.is_ascii_alphabetic()));
+                if is_hex {
+                    return "<hex>".to_owned();
+                }
+                return word.to_owned();
+            })
+            .into_owned();
+        pattern = self.number_regex.replace_all(&pattern, "<n>").into_owned();
+        if let Some((idx, _)) = pattern.char_indices().nth(MAX_PATTERN_LEN) {
+            pattern.truncate(idx);
+        }
+
+        self.counts
+            .entry((loglevel, pattern))
+            .or_insert_with(|| (0, message.to_owned()))
+            .0 += 1;
+        self.evict_rare_patterns();
+    }
+
+    /// Adds the counts of `other`, the examples of `self` are kept as they
+    /// were found first.
+    pub fn merge(&mut self, other: PatternCounter) {
+        for (key, (count, example)) in other.counts {
+            self.counts.entry(key).or_insert((0, example)).0 += count;
+        }
+        self.evict_rare_patterns();
+    }
+
+    // Patterns are dropped in batches so that the counts are not sorted for
+    // every new pattern
+    fn evict_rare_patterns(&mut self) {
+        if self.counts.len() <= 2 * MAX_COUNTED_PATTERNS {
+            return;
+        }
+        self.counts = self
+            .top(MAX_COUNTED_PATTERNS)
+            .into_iter()
+            .map(|pattern| {
+                (
+                    (pattern.loglevel, pattern.pattern),
+                    (pattern.count, pattern.example),
+                )
+            })
+            .collect();
+    }
+
+    /// Counter continuing from the patterns counted by another one, see
+    /// [`PatternCounter::top`].
+    pub fn from_patterns(patterns: Vec<MessagePattern>) -> PatternCounter {
+        let mut counter = PatternCounter::new();
+        for pattern in patterns {
+            counter.counts.insert(
+                (pattern.loglevel, pattern.pattern),
+                (pattern.count, pattern.example),
+            );
+        }
+        return counter;
+    }
+
+    /// The `n` most frequent patterns, the more severe loglevel first among
+    /// patterns as frequent.
+    pub fn top(&self, n: usize) -> Vec<MessagePattern> {
+        let mut counts: Vec<_> = self.counts.iter().collect();
+        // Ties are broken by loglevel and pattern so that the same patterns
+        // are kept whatever the order they were counted in
+        counts.sort_by(
+            |((a_level, a_pattern), (a_count, _)), ((b_level, b_pattern), (b_count, _))| {
+                b_count
+                    .cmp(a_count)
+                    .then(a_level.cmp(b_level))
+                    .then_with(|| a_pattern.cmp(b_pattern))
+            },
+        );
+
+        return counts
+            .into_iter()
+            .take(n)
+            .map(|((loglevel, pattern), (count, example))| MessagePattern {
+                loglevel: *loglevel,
+                pattern: pattern.clone(),
+                count: *count,
+                example: example.clone(),
+            })
+            .collect();
+    }
+}
+
+impl Default for PatternCounter {
+    fn default() -> Self {
+        return PatternCounter::new();
+    }
+}
@@ -0,0 +1,224 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{fs::read_to_string, path::Path};
+
+use chrono::{NaiveDateTime, TimeDelta};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::analyse::{
+    dtfmt::parse_duration,
+    loglevel::LOGLEVELS,
+    metrics::{bucket_end, bucket_width_secs},
+    threshold::{format_value, Condition, Subject},
+    LogsAggregate,
+};
+
+/// Alert rule read from a rules file, evaluated over every window of
+/// consecutive buckets.
+pub struct AlertRule {
+    pub name: String,
+    pub target: RuleTarget,
+    pub window: Option<TimeDelta>,
+    pub condition_text: String,
+    pub condition: Condition,
+    pub severity: Severity,
+}
+
+pub enum RuleTarget {
+    Logs(Subject),
+    /// Logs matching the pattern, whatever their level is
+    Pattern(Regex),
+}
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+/// Time range where a rule was met, overlapping windows are merged.
+#[derive(Serialize)]
+pub struct RuleFiring {
+    pub rule: String,
+    pub severity: Severity,
+    pub condition: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// Value of the window furthest past the limit
+    pub value: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    name: String,
+    level: Option<String>,
+    pattern: Option<String>,
+    window: Option<String>,
+    condition: String,
+    #[serde(default)]
+    severity: Severity,
+}
+
+/// Reads the rules from a toml file, or from a yaml file when the file ends
+/// with `.yaml` or `.yml`.
+pub fn load_rules(rules_path: &str) -> Result<Vec<AlertRule>, String> {
+    let text = read_to_string(rules_path)
+        .map_err(|err| format!("Error reading rules file {}: {}", rules_path, err))?;
+    let is_yaml = matches!(
+        Path::new(rules_path)
+            .extension()
+            .and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    );
+    let rules_file: RulesFile = if is_yaml {
+        serde_yaml::from_str(&text).map_err(|err| err.to_string())?
+    } else {
+        toml::from_str(&text).map_err(|err| err.to_string())?
+    };
+
+    return rules_file
+        .rule
+        .into_iter()
+        .map(|config| {
+            to_alert_rule(config).map_err(|err| format!("Invalid rule in {}: {}", rules_path, err))
+        })
+        .collect();
+}
+
+fn to_alert_rule(config: RuleConfig) -> Result<AlertRule, String> {
+    let target = match (&config.level, &config.pattern) {
+        (Some(level), None) => RuleTarget::Logs(level.parse()?),
+        (None, Some(pattern)) => RuleTarget::Pattern(
+            Regex::new(pattern).map_err(|err| format!("{}: {}", config.name, err))?,
+        ),
+        (None, None) => RuleTarget::Logs(Subject::Total),
+        (Some(_), Some(_)) => {
+            return Err(format!(
+                "{}: give either a level or a pattern, not both",
+                config.name
+            ))
+        }
+    };
+
+    return Ok(AlertRule {
+        target,
+        window: config.window.as_deref().map(parse_duration).transpose()?,
+        condition: config.condition.parse()?,
+        condition_text: config.condition,
+        severity: config.severity,
+        name: config.name,
+    });
+}
+
+/// Evaluates every rule over sliding windows of buckets. `pattern_matches`
+/// holds the datetimes of the logs matching each pattern rule, in the order
+/// of the rules.
+pub fn evaluate_rules(
+    rules: &[AlertRule],
+    logs_aggregate: &LogsAggregate,
+    pattern_matches: &[Vec<NaiveDateTime>],
+) -> Vec<RuleFiring> {
+    let num_dt = logs_aggregate.datetimes.len();
+    if num_dt == 0 {
+        return Vec::new();
+    }
+
+    let bucket_totals: Vec<i32> = (0..num_dt)
+        .map(|idx| {
+            LOGLEVELS
+                .iter()
+                .map(|loglevel| logs_aggregate.aggregates[loglevel][idx])
+                .sum()
+        })
+        .collect();
+    let bucket_widths: Vec<f64> = (0..num_dt)
+        .map(|idx| bucket_width_secs(logs_aggregate, idx))
+        .collect();
+
+    let mut firings: Vec<RuleFiring> = Vec::new();
+    let mut pattern_idx = 0;
+    for rule in rules {
+        let counts: Vec<i32> = match &rule.target {
+            RuleTarget::Logs(subject) => (0..num_dt)
+                .map(|idx| {
+                    LOGLEVELS
+                        .iter()
+                        .filter(|loglevel| subject.includes(loglevel))
+                        .map(|loglevel| logs_aggregate.aggregates[loglevel][idx])
+                        .sum()
+                })
+                .collect(),
+            RuleTarget::Pattern(_) => {
+                let mut counts = vec![0; num_dt];
+                for datetime in &pattern_matches[pattern_idx] {
+                    let idx = logs_aggregate
+                        .datetimes
+                        .partition_point(|bucket| bucket <= datetime);
+                    counts[idx.saturating_sub(1)] += 1;
+                }
+                pattern_idx += 1;
+                counts
+            }
+        };
+
+        // Windows shorter than a bucket can only be looked at a bucket at a time
+        let window_len = match rule.window {
+            Some(window) => (window.num_seconds() as f64 / bucket_widths[0]).ceil() as usize,
+            None => 1,
+        }
+        .clamp(1, num_dt);
+
+        let mut firing: Option<RuleFiring> = None;
+        for first in 0..=(num_dt - window_len) {
+            let window = first..first + window_len;
+            let value = rule.condition.measure(
+                counts[window.clone()].iter().sum(),
+                bucket_totals[window.clone()].iter().sum(),
+                bucket_widths[window.clone()].iter().sum(),
+            );
+
+            if !rule.condition.is_met(value) {
+                continue;
+            }
+            let start = logs_aggregate.datetimes[first];
+            let end = bucket_end(logs_aggregate, window.end - 1);
+            match firing.as_mut() {
+                Some(firing) if start < firing.end => {
+                    firing.end = end;
+                    firing.value = rule.condition.worse(firing.value, value);
+                }
+                _ => {
+                    firings.extend(firing.replace(RuleFiring {
+                        rule: rule.name.clone(),
+                        severity: rule.severity,
+                        condition: rule.condition_text.clone(),
+                        start,
+                        end,
+                        value,
+                    }));
+                }
+            }
+        }
+        firings.extend(firing);
+    }
+
+    return firings;
+}
+
+impl RuleFiring {
+    pub fn value_text(&self) -> String {
+        return format_value(self.value);
+    }
+}
@@ -0,0 +1,349 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::analyse::{
+    loglevel::{is_error_or_above, to_loglevel, LogLevel, LOGLEVELS},
+    metrics::bucket_width_secs,
+    LogsAggregate,
+};
+
+/// A rule like `error>0`, `warning rate>5/min` or `errors share>=10%` that
+/// the analysed logs should not meet.
+#[derive(Clone)]
+pub struct Threshold {
+    rule: String,
+    subject: Subject,
+    condition: Condition,
+}
+
+/// Logs a rule is about.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Subject {
+    Level(LogLevel),
+    /// Error and more severe logs together
+    Errors,
+    Total,
+}
+
+/// What is measured and the limit it is compared to, eg: `rate>5/min`.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Condition {
+    measure: Measure,
+    comparison: Comparison,
+    limit: f64,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Measure {
+    Count,
+    /// Average number of logs per this many seconds
+    Rate(f64),
+    /// Percentage of all the logs
+    Share,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+}
+
+pub struct Violation {
+    pub rule: String,
+    pub value: f64,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{} (was {})", self.rule, format_value(self.value));
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim();
+        let subject_end = rule
+            .find(|c: char| c.is_whitespace() || "<>=".contains(c))
+            .unwrap_or(rule.len());
+        let (subject, condition) = rule.split_at(subject_end);
+
+        return Ok(Threshold {
+            rule: rule.to_owned(),
+            subject: subject.parse()?,
+            condition: condition.parse()?,
+        });
+    }
+}
+
+impl FromStr for Subject {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.trim().to_lowercase();
+        return match name.as_str() {
+            "" => Err("No log level given, eg: error>0".to_owned()),
+            "errors" => Ok(Subject::Errors),
+            "total" | "all" => Ok(Subject::Total),
+            "others" => Ok(Subject::Level(LogLevel::Others)),
+            _ => match to_loglevel(&name) {
+                LogLevel::Others => Err(format!(
+                    "Unknown log level '{}', use a log level, errors or total",
+                    name
+                )),
+                loglevel => Ok(Subject::Level(loglevel)),
+            },
+        };
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(condition: &str) -> Result<Self, Self::Err> {
+        let condition = condition.trim();
+        let op_start = condition
+            .find(['<', '>', '='])
+            .ok_or(format!("No comparison found in '{}', eg: >0", condition))?;
+        let (measure_name, rhs) = condition.split_at(op_start);
+
+        let (comparison, rhs) = [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            ("==", Comparison::Equal),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+            ("=", Comparison::Equal),
+        ]
+        .iter()
+        .find_map(|(op, comparison)| Some((*comparison, rhs.strip_prefix(op)?)))
+        .unwrap();
+        let rhs = rhs.trim();
+
+        let (measure, limit) = match measure_name.trim().to_lowercase().as_str() {
+            "" | "count" => (Measure::Count, rhs),
+            "rate" => {
+                let (limit, per) = rhs
+                    .split_once('/')
+                    .ok_or(format!("Rate should look like 5/min, got '{}'", rhs))?;
+                (Measure::Rate(parse_rate_unit(per.trim())?), limit.trim())
+            }
+            "share" => (Measure::Share, rhs.trim_end_matches('%').trim_end()),
+            other => {
+                return Err(format!(
+                    "Unknown measure '{}', use one of count, rate or share",
+                    other
+                ))
+            }
+        };
+        let limit: f64 = limit
+            .parse()
+            .map_err(|_| format!("Invalid number '{}' in '{}'", limit, condition))?;
+
+        return Ok(Condition {
+            measure,
+            comparison,
+            limit,
+        });
+    }
+}
+
+impl Subject {
+    pub fn includes(&self, loglevel: &LogLevel) -> bool {
+        return match self {
+            Subject::Level(level) => loglevel == level,
+            Subject::Errors => is_error_or_above(loglevel),
+            Subject::Total => true,
+        };
+    }
+}
+
+impl Condition {
+    /// Measured value of `count` logs out of `total` logs in `duration_secs`.
+    pub fn measure(&self, count: i32, total: i32, duration_secs: f64) -> f64 {
+        return match self.measure {
+            Measure::Count => count as f64,
+            Measure::Rate(_) if duration_secs <= 0.0 => 0.0,
+            Measure::Rate(per_secs) => count as f64 / duration_secs * per_secs,
+            Measure::Share if total == 0 => 0.0,
+            Measure::Share => count as f64 * 100.0 / total as f64,
+        };
+    }
+
+    pub fn is_met(&self, value: f64) -> bool {
+        return match self.comparison {
+            Comparison::Greater => value > self.limit,
+            Comparison::GreaterOrEqual => value >= self.limit,
+            Comparison::Less => value < self.limit,
+            Comparison::LessOrEqual => value <= self.limit,
+            Comparison::Equal => value == self.limit,
+        };
+    }
+
+    /// The one of the two values further past the limit.
+    pub fn worse(&self, a: f64, b: f64) -> f64 {
+        return match self.comparison {
+            Comparison::Less | Comparison::LessOrEqual => a.min(b),
+            _ => a.max(b),
+        };
+    }
+}
+
+fn parse_rate_unit(unit: &str) -> Result<f64, String> {
+    return match unit {
+        "s" | "sec" | "second" => Ok(1.0),
+        "m" | "min" | "minute" => Ok(60.0),
+        "h" | "hour" => Ok(3600.0),
+        "d" | "day" => Ok(86400.0),
+        _ => Err(format!(
+            "Unknown rate unit '{}', use one of s, min, h or d",
+            unit
+        )),
+    };
+}
+
+/// Gives the thresholds met by the logs, along with the measured values.
+pub fn evaluate_thresholds(
+    thresholds: &[Threshold],
+    logs_aggregate: &LogsAggregate,
+    combined_loglevel_count: &HashMap<LogLevel, i32>,
+) -> Vec<Violation> {
+    let total: i32 = combined_loglevel_count.values().sum();
+    let duration_secs: f64 = (0..logs_aggregate.datetimes.len())
+        .map(|idx| bucket_width_secs(logs_aggregate, idx))
+        .sum();
+
+    return thresholds
+        .iter()
+        .filter_map(|threshold| {
+            let count: i32 = LOGLEVELS
+                .iter()
+                .filter(|loglevel| threshold.subject.includes(loglevel))
+                .map(|loglevel| combined_loglevel_count[loglevel])
+                .sum();
+            let value = threshold.condition.measure(count, total, duration_secs);
+
+            threshold.condition.is_met(value).then(|| Violation {
+                rule: threshold.rule.clone(),
+                value,
+            })
+        })
+        .collect();
+}
+
+pub fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        return format!("{}", value);
+    }
+    return format!("{:.2}", value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyse::{calc::sum_of_log_occ, dtfmt::DateTimeCat};
+    use chrono::NaiveDate;
+
+    fn parse_error(rule: &str) -> String {
+        return match rule.parse::<Threshold>() {
+            Ok(_) => panic!("'{}' should not parse", rule),
+            Err(err) => err,
+        };
+    }
+
+    #[test]
+    fn parses_rules() {
+        let threshold: Threshold = "error>0".parse().unwrap();
+        assert!(threshold.subject == Subject::Level(LogLevel::Error));
+        assert!(threshold.condition.measure == Measure::Count);
+        assert!(threshold.condition.comparison == Comparison::Greater);
+        assert_eq!(threshold.condition.limit, 0.0);
+
+        let threshold: Threshold = " Warning rate >= 5 / min ".parse().unwrap();
+        assert_eq!(threshold.rule, "Warning rate >= 5 / min");
+        assert!(threshold.subject == Subject::Level(LogLevel::Warning));
+        assert!(threshold.condition.measure == Measure::Rate(60.0));
+        assert!(threshold.condition.comparison == Comparison::GreaterOrEqual);
+        assert_eq!(threshold.condition.limit, 5.0);
+
+        let threshold: Threshold = "errors share<=10.5%".parse().unwrap();
+        assert!(threshold.subject == Subject::Errors);
+        assert!(threshold.condition.measure == Measure::Share);
+        assert!(threshold.condition.comparison == Comparison::LessOrEqual);
+        assert_eq!(threshold.condition.limit, 10.5);
+
+        let threshold: Threshold = "total count=100".parse().unwrap();
+        assert!(threshold.subject == Subject::Total);
+        assert!(threshold.condition.comparison == Comparison::Equal);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(parse_error(">0").starts_with("No log level given"));
+        assert!(parse_error("").starts_with("No log level given"));
+        assert!(parse_error("verbose>0").starts_with("Unknown log level 'verbose'"));
+        assert!(parse_error("error").starts_with("No comparison found"));
+        assert!(parse_error("error rate").starts_with("No comparison found"));
+        assert!(parse_error("error median>1").starts_with("Unknown measure 'median'"));
+        assert!(parse_error("error rate>5").starts_with("Rate should look like 5/min"));
+        assert!(parse_error("error rate>5/week").starts_with("Unknown rate unit 'week'"));
+        assert!(parse_error("error>some").starts_with("Invalid number 'some'"));
+        assert!(parse_error("errors share>ten%").starts_with("Invalid number 'ten'"));
+    }
+
+    #[test]
+    fn measures_and_compares() {
+        let condition: Condition = "rate>5/min".parse().unwrap();
+        assert_eq!(condition.measure(10, 100, 60.0), 10.0);
+        assert_eq!(condition.measure(10, 100, 0.0), 0.0);
+        assert!(condition.is_met(10.0));
+        assert!(!condition.is_met(5.0));
+        assert_eq!(condition.worse(3.0, 7.0), 7.0);
+
+        let condition: Condition = "share<20%".parse().unwrap();
+        assert_eq!(condition.measure(10, 100, 60.0), 10.0);
+        assert_eq!(condition.measure(0, 0, 60.0), 0.0);
+        assert!(condition.is_met(10.0));
+        assert_eq!(condition.worse(3.0, 7.0), 3.0);
+    }
+
+    #[test]
+    fn evaluates_the_rules() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let mut aggregates: HashMap<LogLevel, Vec<i32>> = LOGLEVELS
+            .iter()
+            .map(|loglevel| (*loglevel, vec![0, 0]))
+            .collect();
+        aggregates.insert(LogLevel::Error, vec![4, 6]);
+        aggregates.insert(LogLevel::Info, vec![5, 5]);
+        let logs_aggregate = LogsAggregate {
+            datetimes: vec![start, start + chrono::TimeDelta::minutes(1)],
+            datetime_cat: DateTimeCat::Minutes,
+            aggregates,
+        };
+        let combined_loglevel_count = sum_of_log_occ(&logs_aggregate.aggregates);
+
+        let thresholds: Vec<Threshold> = ["error rate>4/min", "errors share<50%", "total=20"]
+            .iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect();
+        let violations =
+            evaluate_thresholds(&thresholds, &logs_aggregate, &combined_loglevel_count);
+        let violations: Vec<(&str, f64)> = violations
+            .iter()
+            .map(|violation| (violation.rule.as_str(), violation.value))
+            .collect();
+        assert_eq!(
+            violations,
+            vec![("error rate>4/min", 5.0), ("total=20", 20.0)]
+        );
+    }
+}
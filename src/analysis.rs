@@ -0,0 +1,375 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{collections::HashMap, fs::write};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::analyse::{
+    analyse_logs,
+    calc::sum_of_log_occ,
+    dtfmt::DateTimeCat,
+    gaps::GapOptions,
+    loglevel::LogLevel,
+    parser::{LineParseError, LogParser, ParserKind},
+    rules::{AlertRule, RuleTarget},
+    LogSource, LogsAnalysis, SourceCache, REJECTS_SAMPLE_SIZE,
+};
+use crate::input::{InputSource, LineFilter};
+use crate::parallel::default_jobs;
+use crate::state::AnalysisState;
+
+/// How the logs are parsed and analysed, made with [`AnalysisConfig::builder`].
+pub struct AnalysisConfig {
+    datetime_format: String,
+    /// None when the logs are parsed by a parser given with
+    /// [`AnalysisConfigBuilder::log_parser`]
+    parser: Option<ParserKind>,
+    log_parser: Box<dyn LogParser>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    gap_options: GapOptions,
+    rules: Vec<AlertRule>,
+    jobs: usize,
+    syslog_year: Option<i32>,
+    message_patterns: bool,
+}
+
+pub struct AnalysisConfigBuilder {
+    datetime_format: String,
+    parser: ParserKind,
+    log_parser: Option<Box<dyn LogParser>>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    min_gap: Option<TimeDelta>,
+    silence_ratio: f64,
+    rules: Vec<AlertRule>,
+    jobs: usize,
+    syslog_year: Option<i32>,
+    message_patterns: bool,
+}
+
+/// Everything found in the logs, the input of every renderer.
+pub struct Analysis {
+    pub no_of_files: i32,
+    pub no_of_logs: i32,
+    pub logs_analysis: LogsAnalysis,
+    /// Number of logs of every loglevel over all the buckets
+    pub combined_loglevel_count: HashMap<LogLevel, i32>,
+    pub metadata: AnalysisMetadata,
+}
+
+/// How and when an analysis was made, shown in the reports so that they
+/// can be trusted and reproduced.
+#[derive(Serialize)]
+pub struct AnalysisMetadata {
+    pub generator: &'static str,
+    pub version: &'static str,
+    pub generated_at: DateTime<Local>,
+    /// Files or streams the logs were read from
+    pub inputs: Vec<String>,
+    pub parser: Option<ParserKind>,
+    /// Empty when the parser finds the timestamps by itself
+    pub datetime_format: String,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Datetimes of the first and the last log
+    pub first_log: Option<NaiveDateTime>,
+    pub last_log: Option<NaiveDateTime>,
+    /// Width of a time bucket
+    pub granularity: DateTimeCat,
+    /// Lines read after the include and exclude filters
+    pub lines_read: i32,
+    pub lines_unparsed: i32,
+    /// Percentage of the lines read that were parsed
+    pub coverage: f64,
+}
+
+impl AnalysisConfig {
+    /// Starts a config for logs whose datetimes look like `datetime_format`,
+    /// eg: "%Y-%m-%d %H:%M:%S". It can be empty for the parsers that find the
+    /// timestamps of the records themselves.
+    pub fn builder(datetime_format: &str) -> AnalysisConfigBuilder {
+        return AnalysisConfigBuilder {
+            datetime_format: datetime_format.to_owned(),
+            parser: ParserKind::default(),
+            log_parser: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            min_gap: None,
+            silence_ratio: 0.1,
+            rules: Vec::new(),
+            jobs: default_jobs(),
+            syslog_year: None,
+            message_patterns: true,
+        };
+    }
+
+    /// Reads the logs of `source` and analyses them.
+    pub fn analyse(&self, source: &dyn InputSource) -> Result<Analysis, String> {
+        let mut log_sources: Vec<LogSource> = Vec::new();
+        source.read_logs(&mut log_sources)?;
+
+        return Ok(self.analyse_sources(&log_sources, None));
+    }
+
+    /// Same as [`AnalysisConfig::analyse`], but only parses what was appended
+    /// to the files since `state` was saved. Files rotated or truncated since
+    /// are parsed from their start. Returns the state to give to the next
+    /// analysis of the same files.
+    ///
+    /// A state is only used with the same parser, datetime format, filters
+    /// and pattern rules. Custom parsers are told apart from each other by
+    /// nothing, a state made with one should not be given to another.
+    pub fn analyse_incremental(
+        &self,
+        source: &dyn InputSource,
+        state: Option<AnalysisState>,
+    ) -> Result<(Analysis, AnalysisState), String> {
+        let mut log_sources: Vec<LogSource> = Vec::new();
+        source.read_logs(&mut log_sources)?;
+
+        let settings = self.parse_settings();
+        let mut caches: Vec<Option<SourceCache>> = match state {
+            Some(state) => state.resume(&settings, &log_sources),
+            None => log_sources.iter().map(|_| None).collect(),
+        };
+        let analysis = self.analyse_sources(&log_sources, Some(&mut caches));
+
+        return Ok((analysis, AnalysisState::new(settings, &log_sources, caches)));
+    }
+
+    // Options that change what is found in a line, parsed lines are only
+    // reused when they are the same
+    fn parse_settings(&self) -> String {
+        let patterns: Vec<&str> = self
+            .rules
+            .iter()
+            .filter_map(|rule| match &rule.target {
+                RuleTarget::Pattern(pattern) => Some(pattern.as_str()),
+                RuleTarget::Logs(_) => None,
+            })
+            .collect();
+        let include: Vec<&str> = self.include.iter().map(Regex::as_str).collect();
+        let exclude: Vec<&str> = self.exclude.iter().map(Regex::as_str).collect();
+        return format!(
+            "parser={:?} datetime_format={:?} syslog_year={:?} include={:?} exclude={:?} patterns={:?}",
+            self.parser,
+            self.datetime_format,
+            self.syslog_year,
+            include,
+            exclude,
+            patterns
+        );
+    }
+
+    fn analyse_sources(
+        &self,
+        log_sources: &[LogSource],
+        caches: Option<&mut [Option<SourceCache>]>,
+    ) -> Analysis {
+        let line_filter = LineFilter {
+            include: &self.include,
+            exclude: &self.exclude,
+        };
+        let logs_analysis = analyse_logs(
+            log_sources,
+            caches,
+            &line_filter,
+            self.log_parser.as_ref(),
+            self.gap_options,
+            &self.rules,
+            self.message_patterns,
+            self.jobs,
+        );
+
+        let metadata = AnalysisMetadata {
+            generator: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            generated_at: Local::now(),
+            inputs: log_sources
+                .iter()
+                .map(|source| source.name.clone())
+                .collect(),
+            parser: self.parser,
+            datetime_format: self.datetime_format.clone(),
+            include: self.include.iter().map(|re| re.to_string()).collect(),
+            exclude: self.exclude.iter().map(|re| re.to_string()).collect(),
+            first_log: logs_analysis.time_span.map(|(first, _)| first),
+            last_log: logs_analysis.time_span.map(|(_, last)| last),
+            granularity: logs_analysis.logs_aggregate.datetime_cat,
+            lines_read: logs_analysis.no_of_lines,
+            lines_unparsed: logs_analysis.parse_failures.total(),
+            coverage: if logs_analysis.no_of_lines == 0 {
+                0.0
+            } else {
+                (logs_analysis.no_of_lines - logs_analysis.parse_failures.total()) as f64 * 100.0
+                    / logs_analysis.no_of_lines as f64
+            },
+        };
+
+        return Analysis {
+            no_of_files: log_sources.len() as i32,
+            no_of_logs: logs_analysis.no_of_lines,
+            combined_loglevel_count: sum_of_log_occ(&logs_analysis.logs_aggregate.aggregates),
+            logs_analysis,
+            metadata,
+        };
+    }
+}
+
+impl Analysis {
+    /// Writes the rejected lines kept in the analysis to `path`, one per line
+    /// as `<file>:<line number>: <reason>: <line>`.
+    pub fn write_rejects(&self, path: &str) -> Result<(), String> {
+        let logs_analysis = &self.logs_analysis;
+        let mut text = String::new();
+        for rejected in &logs_analysis.rejected_lines {
+            text += &format!(
+                "{}:{}: {}: {}\n",
+                rejected.source, rejected.line_number, rejected.reason, rejected.line
+            );
+        }
+        let parse_failures = &logs_analysis.parse_failures;
+        for (reason, count) in [
+            (
+                LineParseError::NoDatetimeMatch,
+                parse_failures.no_datetime_match,
+            ),
+            (
+                LineParseError::DatetimeParseError,
+                parse_failures.datetime_parse_error,
+            ),
+            (
+                LineParseError::MalformedRecord,
+                parse_failures.malformed_record,
+            ),
+        ] {
+            if count > REJECTS_SAMPLE_SIZE {
+                text += &format!(
+                    "# only the first {} of the {} lines with {} are listed\n",
+                    REJECTS_SAMPLE_SIZE, count, reason
+                );
+            }
+        }
+
+        return write(path, text)
+            .map_err(|err| format!("Error writing rejected lines to {}: {}", path, err));
+    }
+}
+
+impl AnalysisMetadata {
+    /// Name of the parser as given to `--parser`, "custom" for a parser
+    /// given with [`AnalysisConfigBuilder::log_parser`].
+    pub fn parser_name(&self) -> String {
+        return match self.parser {
+            Some(parser) => format!("{:?}", parser).to_lowercase(),
+            None => "custom".to_owned(),
+        };
+    }
+}
+
+impl AnalysisConfigBuilder {
+    /// Format of the log lines, the regex parser by default.
+    pub fn parser(mut self, parser: ParserKind) -> Self {
+        self.parser = parser;
+        return self;
+    }
+
+    /// Parses the lines with a parser of another format instead.
+    pub fn log_parser(mut self, log_parser: Box<dyn LogParser>) -> Self {
+        self.log_parser = Some(log_parser);
+        return self;
+    }
+
+    /// Only analyses the lines matching `pattern`, or any of the other
+    /// include patterns.
+    pub fn include(mut self, pattern: Regex) -> Self {
+        self.include.push(pattern);
+        return self;
+    }
+
+    /// Skips the lines matching `pattern`.
+    pub fn exclude(mut self, pattern: Regex) -> Self {
+        self.exclude.push(pattern);
+        return self;
+    }
+
+    /// Shortest period without logs reported as a gap, two buckets by default.
+    pub fn gap_threshold(mut self, min_gap: TimeDelta) -> Self {
+        self.min_gap = Some(min_gap);
+        return self;
+    }
+
+    /// Share of the usual log rate below which a period is reported as a low
+    /// volume gap, 0.1 by default.
+    pub fn silence_ratio(mut self, silence_ratio: f64) -> Self {
+        self.silence_ratio = silence_ratio;
+        return self;
+    }
+
+    /// Alert rules evaluated over the analysed logs, see [`crate::analyse::rules::load_rules`].
+    pub fn rules(mut self, rules: Vec<AlertRule>) -> Self {
+        self.rules = rules;
+        return self;
+    }
+
+    /// Number of threads the lines are parsed on, one per CPU core by default.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        return self;
+    }
+
+    /// Year of the syslog timestamps that have none, by default the year of
+    /// the last write to their file or the year before.
+    pub fn syslog_year(mut self, year: i32) -> Self {
+        self.syslog_year = Some(year);
+        return self;
+    }
+
+    /// Whether the most frequent message patterns are counted, on by default.
+    /// It takes a few regex replacements on every line, turn it off when no
+    /// report shows them. They are counted anyway by
+    /// [`AnalysisConfig::analyse_incremental`], as the state keeps them for the
+    /// next analysis.
+    pub fn message_patterns(mut self, message_patterns: bool) -> Self {
+        self.message_patterns = message_patterns;
+        return self;
+    }
+
+    pub fn build(self) -> Result<AnalysisConfig, String> {
+        if self.log_parser.is_none()
+            && self.parser.needs_datetime_format()
+            && self.datetime_format.is_empty()
+        {
+            return Err("Empty datetime string format given".to_owned());
+        }
+        if !(0.0..=1.0).contains(&self.silence_ratio) {
+            return Err("Silence ratio should be between 0 and 1".to_owned());
+        }
+        if self.jobs == 0 {
+            return Err("Number of jobs should be greater than zero".to_owned());
+        }
+
+        let parser = self.log_parser.is_none().then_some(self.parser);
+        return Ok(AnalysisConfig {
+            log_parser: self.log_parser.unwrap_or_else(|| {
+                self.parser
+                    .make_parser(&self.datetime_format, self.syslog_year)
+            }),
+            datetime_format: self.datetime_format,
+            parser,
+            include: self.include,
+            exclude: self.exclude,
+            gap_options: GapOptions {
+                min_gap: self.min_gap,
+                silence_ratio: self.silence_ratio,
+            },
+            rules: self.rules,
+            jobs: self.jobs,
+            syslog_year: self.syslog_year,
+            message_patterns: self.message_patterns,
+        });
+    }
+}
@@ -1,31 +1,220 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use clap::Parser;
+use chrono::TimeDelta;
+use clap::{Parser, ValueEnum};
+use regex::Regex;
+use serde::Deserialize;
+
+use loggregate::analyse::{dtfmt::parse_duration, parser::ParserKind, threshold::Threshold};
+use loggregate::plot::{PlotFormat, YScale};
 
 #[derive(Parser)]
 #[command(version)]
 #[command(about = "Aggregates, Analyses and Generates reports from log files", long_about = None)]
 pub struct Cli {
     /// Datetime String format eg: "%d/%m/%y %H:%M:%S"
-    #[arg(short, long = "datetime-format")]
+    #[arg(
+        short,
+        long = "datetime-format",
+        default_value = "",
+        hide_default_value = true
+    )]
     pub datetime_str_format: String,
 
+    /// Format of the log lines, structured formats carry their own timestamps and
+    /// levels so the datetime format is optional with them
+    #[arg(short, long = "parser", value_enum, default_value_t = ParserKind::Regex)]
+    pub parser: ParserKind,
+
+    /// Year of syslog timestamps without one, by default the year the file was last written
+    /// to, or the year before for the logs that would come after that
+    #[arg(long = "syslog-year")]
+    pub syslog_year: Option<i32>,
+
     /// User can optionally give their name and it will be shown in the report
     #[arg(short, long = "user", required = false)]
     pub user: Option<String>,
 
-    /// Glob pattern to read log files eg: "./*.log"
-    pub glob_pattern: String,
+    /// Output formats of the report, several can be given eg: "html,json"
+    #[arg(short, long = "format", value_enum, value_delimiter = ',', default_values_t = [OutputFormat::Html])]
+    pub format: Vec<OutputFormat>,
+
+    /// Embed the plots into report.html so that it can be shared as a single file
+    #[arg(long = "self-contained")]
+    pub self_contained: bool,
+
+    /// Draw the log trends as an interactive chart with zoom, hover and level toggling
+    #[arg(long = "interactive")]
+    pub interactive: bool,
+
+    /// Jinja template file the html report is rendered with instead of the built-in one
+    #[arg(long = "template")]
+    pub template: Option<String>,
+
+    /// Scale of the y axis in the stacked timeline plot
+    #[arg(long = "y-scale", value_enum, default_value_t = YScale::Linear)]
+    pub y_scale: YScale,
+
+    /// Image format of the plots in the html report
+    #[arg(long = "plot-format", value_enum, default_value_t = PlotFormat::default())]
+    pub plot_format: PlotFormat,
+
+    /// Size of the plots in pixels at 96 DPI eg: "1920x1080"
+    #[arg(long = "plot-size", default_value = "1280x720", value_parser = parse_plot_size)]
+    pub plot_size: (u32, u32),
+
+    /// Resolution of png plots, the plot size and fonts are scaled along with it
+    #[arg(long = "plot-dpi", default_value_t = 96)]
+    pub plot_dpi: u32,
+
+    /// Report periods without logs longer than this eg: "30s", "10m", "2h", "1d".
+    /// Defaults to the width of two time buckets
+    #[arg(long = "gap-threshold", value_parser = parse_duration)]
+    pub gap_threshold: Option<TimeDelta>,
+
+    /// Report periods where the log rate falls below this share of the usual rate
+    #[arg(long = "silence-ratio", default_value_t = 0.1)]
+    pub silence_ratio: f64,
+
+    /// Exit with an error when the logs meet this rule eg: "error>0", "warning rate>5/min",
+    /// "errors share>=10%". Can be given several times
+    #[arg(long = "fail-on")]
+    pub fail_on: Vec<Threshold>,
+
+    /// Number of threads the logs are parsed on, defaults to the number of CPU cores
+    #[arg(short, long = "jobs")]
+    pub jobs: Option<usize>,
+
+    /// Write the lines that could not be parsed to this file along with their file and
+    /// line number, the first 1000 of every failure reason are kept
+    #[arg(long = "rejects")]
+    pub rejects: Option<String>,
+
+    /// Map the log files into memory instead of reading them. Saves memory on large files,
+    /// but a file truncated during the run, eg: by a copytruncate rotation, crashes loggregate
+    #[arg(long = "mmap")]
+    pub mmap: bool,
+
+    /// Keep what was parsed of every file in this state file, later runs with it only parse
+    /// the lines appended since. Rotated and truncated files are parsed again
+    #[arg(long = "state")]
+    pub state: Option<String>,
+
+    /// Exit with an error before writing the report when more lines than this could not be parsed
+    #[arg(long = "max-errors")]
+    pub max_errors: Option<i32>,
+
+    /// Toml or yaml file with alert rules, their firings are listed in the report
+    #[arg(long = "rules")]
+    pub rules: Option<String>,
+
+    /// Keep following the log files and show a live dashboard of the latest logs in the terminal
+    #[arg(long = "follow")]
+    pub follow: bool,
+
+    /// How often the dashboard is redrawn in follow mode eg: "2s"
+    #[arg(long = "follow-interval", default_value = "2s", value_parser = parse_duration)]
+    pub follow_interval: TimeDelta,
+
+    /// Width of a time bucket in follow mode eg: "10s", "1m"
+    #[arg(long = "follow-bucket", default_value = "1m", value_parser = parse_duration)]
+    pub follow_bucket: TimeDelta,
+
+    /// Number of the latest time buckets shown in follow mode
+    #[arg(long = "follow-window", default_value_t = 30)]
+    pub follow_window: usize,
+
+    /// Read the options from this file instead of ./loggregate.toml, the options given
+    /// on the command line take precedence over the file
+    #[arg(long = "config")]
+    pub config: Option<String>,
+
+    /// Use the options of this profile of the config file eg: "nginx" for [profile.nginx]
+    #[arg(long = "profile")]
+    pub profile: Option<String>,
+
+    /// Directory the report is written to
+    #[arg(short, long = "output-dir", default_value = "report")]
+    pub output_dir: String,
+
+    /// Only analyse the lines matching this regex, can be given several times
+    #[arg(long = "include")]
+    pub include: Vec<Regex>,
+
+    /// Skip the lines matching this regex, can be given several times
+    #[arg(long = "exclude")]
+    pub exclude: Vec<Regex>,
+
+    /// Glob patterns to read log files eg: "./*.log"
+    pub glob_patterns: Vec<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// HTML page with plots of the log trends
+    Html,
+    /// Machine readable JSON document with the aggregated counts
+    Json,
+    /// CSV table with one row per time bucket and one column per loglevel
+    Csv,
+    /// Same table as csv, stored as a parquet file
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// Totals, trends and the counts of every bucket printed to the terminal
+    Text,
+    /// Markdown summary that can be posted as a comment on a pull request
+    #[value(alias = "md")]
+    #[serde(alias = "md")]
+    Markdown,
+}
+
+fn parse_plot_size(size: &str) -> Result<(u32, u32), String> {
+    let (width, height) = size.split_once('x').ok_or(format!(
+        "Plot size should look like 1280x720, got '{}'",
+        size
+    ))?;
+    let width: u32 = width
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid plot width '{}'", width))?;
+    let height: u32 = height
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid plot height '{}'", height))?;
+
+    return Ok((width, height));
 }
 
 pub fn validate_input(input: &Cli) -> Result<bool, &str> {
-    if input.glob_pattern.is_empty() {
+    if input.glob_patterns.iter().all(|pattern| pattern.is_empty()) {
         return Err("No file pattern given, give a valid file pattern");
     }
 
-    if input.datetime_str_format.is_empty() {
+    if input.parser.needs_datetime_format() && input.datetime_str_format.is_empty() {
         return Err("Empty datetime string format given, give a valid datetime format");
     }
 
+    if input.plot_size.0 == 0 || input.plot_size.1 == 0 || input.plot_dpi == 0 {
+        return Err("Plot size and DPI should be greater than zero");
+    }
+
+    if !(0.0..=1.0).contains(&input.silence_ratio) {
+        return Err("Silence ratio should be between 0 and 1");
+    }
+
+    if input.follow_interval <= TimeDelta::zero()
+        || input.follow_bucket <= TimeDelta::zero()
+        || input.follow_window == 0
+    {
+        return Err("Follow interval, bucket and window should be greater than zero");
+    }
+
+    if input.plot_format == PlotFormat::Png && !cfg!(feature = "ttf") {
+        return Err(
+            "Png plots need loggregate to be built with the ttf feature, use svg plots instead",
+        );
+    }
+
     Ok(true)
 }
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use clap::{parser::ValueSource, ArgMatches};
+use glob::Pattern;
+use regex::Regex;
+use serde::Deserialize;
+
+use loggregate::analyse::parser::ParserKind;
+
+use crate::cli::{Cli, OutputFormat};
+
+/// Config file looked up in the current directory when `--config` is not given
+pub const DEFAULT_CONFIG_FILE: &str = "loggregate.toml";
+
+/// Options that can be kept in the config file, every one of them can also
+/// be set in a `[profile.<name>]` table.
+#[derive(Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RunConfig {
+    datetime_format: Option<String>,
+    parser: Option<ParserKind>,
+    syslog_year: Option<i32>,
+    user: Option<String>,
+    globs: Option<Vec<String>>,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    output_dir: Option<String>,
+    format: Option<Formats>,
+    rules: Option<String>,
+    template: Option<String>,
+    state: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+enum Formats {
+    One(OutputFormat),
+    Many(Vec<OutputFormat>),
+}
+
+/// Reads the config file given with `--config`, or `loggregate.toml` in the
+/// current directory when it exists, and merges the selected profile into it.
+pub fn load_config(
+    config_path: Option<&str>,
+    profile: Option<&str>,
+) -> Result<Option<RunConfig>, String> {
+    let config_path = match config_path {
+        Some(config_path) => config_path,
+        None if Path::new(DEFAULT_CONFIG_FILE).is_file() => DEFAULT_CONFIG_FILE,
+        None if profile.is_some() => {
+            return Err(format!(
+                "A profile was given but there is no {} here, give one with --config",
+                DEFAULT_CONFIG_FILE
+            ))
+        }
+        None => return Ok(None),
+    };
+
+    let text = read_to_string(config_path)
+        .map_err(|err| format!("Error reading config file {}: {}", config_path, err))?;
+    let mut table: toml::Table = toml::from_str(&text)
+        .map_err(|err| format!("Error parsing config file {}: {}", config_path, err))?;
+
+    let profiles: HashMap<String, RunConfig> = match table.remove("profile") {
+        Some(profiles) => profiles
+            .try_into()
+            .map_err(|err| format!("Invalid profile in {}: {}", config_path, err))?,
+        None => HashMap::new(),
+    };
+    let config: RunConfig = toml::Value::Table(table)
+        .try_into()
+        .map_err(|err| format!("Invalid option in {}: {}", config_path, err))?;
+
+    let config = match profile {
+        Some(name) => match profiles.get(name) {
+            Some(profile) => profile.clone().or(config),
+            None => return Err(format!("No profile '{}' in {}", name, config_path)),
+        },
+        None => config,
+    };
+    let config_dir = Path::new(config_path).parent().unwrap_or(Path::new(""));
+    return Ok(Some(config.resolve_paths(config_dir)));
+}
+
+impl RunConfig {
+    // Options set in `self` win over the ones in `fallback`
+    fn or(self, fallback: RunConfig) -> RunConfig {
+        return RunConfig {
+            datetime_format: self.datetime_format.or(fallback.datetime_format),
+            parser: self.parser.or(fallback.parser),
+            syslog_year: self.syslog_year.or(fallback.syslog_year),
+            user: self.user.or(fallback.user),
+            globs: self.globs.or(fallback.globs),
+            include: self.include.or(fallback.include),
+            exclude: self.exclude.or(fallback.exclude),
+            output_dir: self.output_dir.or(fallback.output_dir),
+            format: self.format.or(fallback.format),
+            rules: self.rules.or(fallback.rules),
+            template: self.template.or(fallback.template),
+            state: self.state.or(fallback.state),
+        };
+    }
+
+    // Relative paths in the config file are relative to the directory of the
+    // file, so that it works the same from wherever loggregate is run
+    fn resolve_paths(mut self, config_dir: &Path) -> RunConfig {
+        if config_dir.as_os_str().is_empty() {
+            return self;
+        }
+        let resolve = |path: String| -> String {
+            if Path::new(&path).is_absolute() {
+                return path;
+            }
+            return config_dir.join(path).to_string_lossy().into_owned();
+        };
+        // The directory may have characters that mean something in a glob
+        let glob_dir = PathBuf::from(Pattern::escape(&config_dir.to_string_lossy()));
+        self.globs = self.globs.map(|globs| {
+            globs
+                .into_iter()
+                .map(|glob| {
+                    if Path::new(&glob).is_absolute() {
+                        return glob;
+                    }
+                    return glob_dir.join(glob).to_string_lossy().into_owned();
+                })
+                .collect()
+        });
+        self.output_dir = self.output_dir.map(resolve);
+        self.rules = self.rules.map(resolve);
+        self.template = self.template.map(resolve);
+        self.state = self.state.map(resolve);
+        return self;
+    }
+}
+
+/// Fills the options not given on the command line from the config.
+pub fn apply_config(
+    input: &mut Cli,
+    matches: &ArgMatches,
+    config: RunConfig,
+) -> Result<(), String> {
+    let is_unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+    if let Some(datetime_format) = config
+        .datetime_format
+        .filter(|_| is_unset("datetime_str_format"))
+    {
+        input.datetime_str_format = datetime_format;
+    }
+    if let Some(parser) = config.parser.filter(|_| is_unset("parser")) {
+        input.parser = parser;
+    }
+    if let Some(syslog_year) = config.syslog_year.filter(|_| is_unset("syslog_year")) {
+        input.syslog_year = Some(syslog_year);
+    }
+    if let Some(user) = config.user.filter(|_| is_unset("user")) {
+        input.user = Some(user);
+    }
+    if let Some(globs) = config.globs.filter(|_| is_unset("glob_patterns")) {
+        input.glob_patterns = globs;
+    }
+    if let Some(include) = config.include.filter(|_| is_unset("include")) {
+        input.include = compile_patterns(&include)?;
+    }
+    if let Some(exclude) = config.exclude.filter(|_| is_unset("exclude")) {
+        input.exclude = compile_patterns(&exclude)?;
+    }
+    if let Some(output_dir) = config.output_dir.filter(|_| is_unset("output_dir")) {
+        input.output_dir = output_dir;
+    }
+    if let Some(formats) = config.format.filter(|_| is_unset("format")) {
+        input.format = match formats {
+            Formats::One(format) => vec![format],
+            Formats::Many(formats) => formats,
+        };
+    }
+    if let Some(rules) = config.rules.filter(|_| is_unset("rules")) {
+        input.rules = Some(rules);
+    }
+    if let Some(template) = config.template.filter(|_| is_unset("template")) {
+        input.template = Some(template);
+    }
+    if let Some(state) = config.state.filter(|_| is_unset("state")) {
+        input.state = Some(state);
+    }
+
+    return Ok(());
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    return patterns
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(|err| format!("Invalid filter: {}", err)))
+        .collect();
+}
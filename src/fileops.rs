@@ -1,33 +1,12 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use fs_extra::dir::{copy, CopyOptions};
-use glob::glob;
 use std::{
     env::{current_dir, temp_dir},
-    fs::{create_dir_all, read_to_string, remove_dir_all},
+    fs::{create_dir_all, read_dir, remove_dir_all, remove_file},
     path::Path,
 };
 
-pub fn read_logs(file_pattern: &str, log_lines: &mut Vec<String>) -> i32 {
-    let mut file_paths: Vec<String> = Vec::new();
-    for entry in glob(file_pattern).expect("Failed to read file pattern") {
-        match entry {
-            Ok(path) => file_paths.push(path.to_str().unwrap().to_owned()),
-            Err(e) => {
-                panic!("{:?}", e);
-            }
-        }
-    }
-
-    for path in &file_paths {
-        for line in read_to_string(path).unwrap().lines() {
-            log_lines.push(line.to_owned());
-        }
-    }
-
-    return file_paths.len() as i32;
-}
-
 pub fn prepare_tmp_loggregate_dir() -> String {
     let tmp_dir = temp_dir().to_str().unwrap().to_owned();
 
@@ -35,6 +14,7 @@ pub fn prepare_tmp_loggregate_dir() -> String {
     if Path::new(&tmp_logrregate_dir).is_dir() {
         remove_dir_all(&tmp_logrregate_dir).expect("Error while cleaning tmp directory");
     }
+    create_dir_all(&tmp_logrregate_dir).expect("Error while creating tmp directory");
 
     return tmp_logrregate_dir;
 }
@@ -45,14 +25,23 @@ pub fn prepare_plots_gen_dir(tmp_loggregate_dir: &str) -> String {
     return plots_gen_dir;
 }
 
-pub fn prepare_report_destination_dir() -> String {
-    let cur_dir = current_dir().unwrap().to_str().unwrap().to_owned();
-    let report_dest_dir = format!("{}/{}", cur_dir, "report");
-    if Path::new(&report_dest_dir).is_dir() {
-        remove_dir_all(&report_dest_dir).expect("Error while cleaning up report directory");
-    }
+// The output directory may hold other files, so only the files of an earlier
+// report are cleaned up.
+pub fn prepare_report_destination_dir(output_dir: &str) -> String {
+    let report_dest_dir = current_dir().unwrap().join(output_dir);
     create_dir_all(&report_dest_dir).expect("Error while creating report directory");
-    return report_dest_dir;
+
+    for entry in read_dir(&report_dest_dir).expect("Error while reading report directory") {
+        let path = entry.unwrap().path();
+        let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if path.is_dir() && file_name == "plots" {
+            remove_dir_all(&path).expect("Error while cleaning up report directory");
+        } else if path.is_file() && file_name.starts_with("report.") {
+            remove_file(&path).expect("Error while cleaning up report directory");
+        }
+    }
+
+    return report_dest_dir.to_str().unwrap().to_owned();
 }
 
 pub fn copy_reports_to_destination(tmp_loggregate_dir: &str, report_destination_dir: &str) {
@@ -60,3 +49,7 @@ pub fn copy_reports_to_destination(tmp_loggregate_dir: &str, report_destination_
     copy(tmp_loggregate_dir, report_destination_dir, &options)
         .expect("Error while copying report to destination");
 }
+
+pub fn remove_plots_gen_dir(plots_gen_dir: &str) {
+    remove_dir_all(plots_gen_dir).expect("Error while removing plots directory");
+}
@@ -0,0 +1,344 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{metadata, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    thread::sleep,
+};
+
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use glob::glob;
+use regex::Regex;
+
+use crate::analyse::{
+    loglevel::{make_loglevel_count_vec_map, LogLevel, LOGLEVELS},
+    parser::ParserKind,
+};
+use crate::input::{file_id, LineFilter};
+use crate::plot::get_color;
+use crate::term::{colorize, sparkline, use_colors};
+
+pub struct FollowOptions {
+    pub glob_patterns: Vec<String>,
+    pub include: Vec<Regex>,
+    pub exclude: Vec<Regex>,
+    pub parser: ParserKind,
+    pub datetime_format: String,
+    pub syslog_year: Option<i32>,
+    pub interval: TimeDelta,
+    pub bucket_width: TimeDelta,
+    pub window: usize,
+}
+
+/// Most bytes read from a file at once, a file that grew by more is read in
+/// several chunks
+const READ_CHUNK_BYTES: u64 = 8 << 20;
+
+struct TailedFile {
+    path: String,
+    file_id: Option<u64>,
+    offset: u64,
+    // Bytes of a line that is still being written
+    pending: Vec<u8>,
+    no_of_lines: usize,
+    rotations: usize,
+    truncations: usize,
+    // Inode, offset and pending bytes of the file that was at `path` before
+    // it was rotated, kept until the next poll in case it turns up under
+    // another name the glob matches
+    rotated_away: Option<(u64, u64, Vec<u8>)>,
+}
+
+// Counts of the last `capacity` buckets, the newest bucket is at the back.
+struct RollingWindow {
+    bucket_secs: i64,
+    capacity: usize,
+    // Bucket index of the newest bucket, none until the first log comes in
+    newest: Option<i64>,
+    counts: HashMap<LogLevel, VecDeque<i32>>,
+}
+
+/// Tails the files matching the glob pattern and redraws a dashboard of the
+/// last buckets in the terminal until the program is stopped.
+pub fn follow_logs(options: FollowOptions) {
+    let log_parser = options
+        .parser
+        .make_parser(&options.datetime_format, options.syslog_year);
+    let line_filter = LineFilter {
+        include: &options.include,
+        exclude: &options.exclude,
+    };
+    let mut window = RollingWindow::new(options.bucket_width, options.window);
+    let mut tailed_files: Vec<TailedFile> = Vec::new();
+    let mut no_of_skipped: usize = 0;
+    // Logs are often not written in local time, so the window follows the
+    // clock shifted by how far the newest log was from it
+    let mut newest_log: Option<NaiveDateTime> = None;
+    let mut clock_offset = TimeDelta::zero();
+
+    let mut first_poll = true;
+    loop {
+        for path in match_files(&options.glob_patterns) {
+            if tailed_files.iter().any(|tailed| tailed.path == path) {
+                continue;
+            }
+            let path_id = path_file_id(&path);
+            let same_file = tailed_files
+                .iter_mut()
+                .find(|tailed| path_id.is_some() && tailed.file_id == path_id);
+            // A file rotated to a name the glob also matches, eg: app.log to
+            // app.log.1, goes on from where it was read. The same file matched
+            // under two names is only followed under the first.
+            if let Some(tailed) = same_file {
+                if path_file_id(&tailed.path) != path_id {
+                    tailed.path = path;
+                    tailed.rotations += 1;
+                }
+                continue;
+            }
+
+            // Files present at start are tailed from their end, like `tail -f`
+            let Some(mut tailed) = TailedFile::open(&path, first_poll) else {
+                continue;
+            };
+            let rotated_away = tailed_files.iter_mut().find_map(|tailed| {
+                tailed
+                    .rotated_away
+                    .take_if(|(rotated_id, _, _)| Some(*rotated_id) == path_id)
+            });
+            if let Some((_, offset, pending)) = rotated_away {
+                tailed.offset = offset;
+                tailed.pending = pending;
+                tailed.rotations = 1;
+            }
+            tailed_files.push(tailed);
+        }
+        for tailed in tailed_files.iter_mut() {
+            tailed.rotated_away = None;
+        }
+        tailed_files.retain(|tailed| Path::new(&tailed.path).exists());
+        first_poll = false;
+
+        for tailed in tailed_files.iter_mut() {
+            while let Ok(Some(lines)) = tailed.read_new_lines() {
+                for line in lines.iter().filter(|line| line_filter.keeps(line)) {
+                    match log_parser.parse(line) {
+                        Ok(record) => {
+                            let datetime = record.datetime;
+                            if newest_log.is_none_or(|newest| datetime > newest) {
+                                newest_log = Some(datetime);
+                                clock_offset = datetime - Local::now().naive_local();
+                            }
+                            window.add(&datetime, &record.loglevel);
+                        }
+                        Err(_) => no_of_skipped += 1,
+                    }
+                }
+            }
+        }
+
+        if newest_log.is_some() {
+            window.advance_to(&(Local::now().naive_local() + clock_offset));
+        }
+        print!(
+            "{}",
+            render_dashboard(&window, &tailed_files, no_of_skipped, use_colors())
+        );
+
+        sleep(options.interval.to_std().unwrap());
+    }
+}
+
+fn path_file_id(path: &str) -> Option<u64> {
+    return metadata(path)
+        .ok()
+        .and_then(|file_metadata| file_id(&file_metadata));
+}
+
+fn match_files(glob_patterns: &[String]) -> Vec<String> {
+    return glob_patterns
+        .iter()
+        .filter_map(|glob_pattern| glob(glob_pattern).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+}
+
+impl TailedFile {
+    fn open(path: &str, from_end: bool) -> Option<TailedFile> {
+        let file_metadata = metadata(path).ok()?;
+        return Some(TailedFile {
+            path: path.to_owned(),
+            file_id: file_id(&file_metadata),
+            offset: if from_end { file_metadata.len() } else { 0 },
+            pending: Vec::new(),
+            no_of_lines: 0,
+            rotations: 0,
+            truncations: 0,
+            rotated_away: None,
+        });
+    }
+
+    /// Reads the lines in the next chunk of at most [`READ_CHUNK_BYTES`]
+    /// appended since the last call, none once everything was read. A
+    /// rotated file is read from its start, and so is a file truncated below
+    /// the read offset.
+    fn read_new_lines(&mut self) -> io::Result<Option<Vec<String>>> {
+        let file_metadata = metadata(&self.path)?;
+        if file_id(&file_metadata) != self.file_id {
+            self.rotated_away = self
+                .file_id
+                .map(|rotated_id| (rotated_id, self.offset, std::mem::take(&mut self.pending)));
+            self.file_id = file_id(&file_metadata);
+            self.offset = 0;
+            self.pending.clear();
+            self.rotations += 1;
+        } else if file_metadata.len() < self.offset {
+            self.offset = 0;
+            self.pending.clear();
+            self.truncations += 1;
+        }
+        if file_metadata.len() == self.offset {
+            return Ok(None);
+        }
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let no_of_bytes = file.take(READ_CHUNK_BYTES).read_to_end(&mut self.pending)?;
+        if no_of_bytes == 0 {
+            return Ok(None);
+        }
+        self.offset += no_of_bytes as u64;
+
+        let Some(last_newline) = self.pending.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(Some(Vec::new()));
+        };
+        let complete: Vec<u8> = self.pending.drain(..=last_newline).collect();
+        let lines: Vec<String> = String::from_utf8_lossy(&complete)
+            .lines()
+            .map(|line| line.to_owned())
+            .collect();
+        self.no_of_lines += lines.len();
+
+        return Ok(Some(lines));
+    }
+}
+
+impl RollingWindow {
+    fn new(bucket_width: TimeDelta, capacity: usize) -> RollingWindow {
+        return RollingWindow {
+            bucket_secs: bucket_width.num_seconds(),
+            capacity,
+            newest: None,
+            counts: make_loglevel_count_vec_map()
+                .into_keys()
+                .map(|loglevel| (loglevel, VecDeque::from(vec![0; capacity])))
+                .collect(),
+        };
+    }
+
+    fn bucket_of(&self, datetime: &NaiveDateTime) -> i64 {
+        return datetime.and_utc().timestamp().div_euclid(self.bucket_secs);
+    }
+
+    fn advance_to(&mut self, datetime: &NaiveDateTime) {
+        let bucket = self.bucket_of(datetime);
+        let newest = self.newest.unwrap_or(bucket);
+        if bucket < newest || self.newest == Some(bucket) {
+            return;
+        }
+
+        let shift = (bucket - newest).min(self.capacity as i64);
+        for counts in self.counts.values_mut() {
+            for _ in 0..shift {
+                counts.pop_front();
+                counts.push_back(0);
+            }
+        }
+        self.newest = Some(bucket);
+    }
+
+    fn add(&mut self, datetime: &NaiveDateTime, loglevel: &LogLevel) {
+        self.advance_to(datetime);
+        let Some(newest) = self.newest else {
+            return;
+        };
+        let age = newest - self.bucket_of(datetime);
+        if age >= self.capacity as i64 {
+            return;
+        }
+
+        let counts = self.counts.get_mut(loglevel).unwrap();
+        counts[self.capacity - 1 - age as usize] += 1;
+    }
+
+    fn bucket_start(&self, bucket: i64) -> NaiveDateTime {
+        return chrono::DateTime::from_timestamp(bucket * self.bucket_secs, 0)
+            .unwrap()
+            .naive_utc();
+    }
+}
+
+fn render_dashboard(
+    window: &RollingWindow,
+    tailed_files: &[TailedFile],
+    no_of_skipped: usize,
+    colors: bool,
+) -> String {
+    let dt_fmt = if window.bucket_secs < 60 {
+        "%Y-%m-%d %H:%M:%S"
+    } else {
+        "%Y-%m-%d %H:%M"
+    };
+
+    // Clear the screen and move the cursor to the top left
+    let mut text = String::from("\x1b[2J\x1b[H");
+    text += &format!(
+        "Loggregate following {} files, {} buckets of {}s",
+        tailed_files.len(),
+        window.capacity,
+        window.bucket_secs,
+    );
+    match window.newest {
+        Some(newest) => {
+            let oldest = newest - window.capacity as i64 + 1;
+            text += &format!(
+                ", from {} to {}\n\n",
+                window.bucket_start(oldest).format(dt_fmt),
+                window.bucket_start(newest).format(dt_fmt),
+            );
+        }
+        None => text += ", waiting for new logs\n\n",
+    }
+
+    text += &format!("{:<10} {:>8} {:>8}  Trend\n", "Level", "Window", "Latest");
+    for loglevel in LOGLEVELS.iter() {
+        let counts: Vec<i32> = window.counts[loglevel].iter().copied().collect();
+        let total: i32 = counts.iter().sum();
+        let name = format!("{:<10}", format!("{:?}", loglevel));
+        text += &format!(
+            "{} {:>8} {:>8}  {}\n",
+            colorize(&name, &get_color(loglevel), colors),
+            total,
+            counts.last().unwrap_or(&0),
+            colorize(&sparkline(&counts), &get_color(loglevel), colors),
+        );
+    }
+
+    text += &format!(
+        "\n{} lines without a valid datetime were skipped\n\n",
+        no_of_skipped
+    );
+    for tailed in tailed_files {
+        text += &format!(
+            "{}: {} lines, rotated {} times, truncated {} times\n",
+            tailed.path, tailed.no_of_lines, tailed.rotations, tailed.truncations
+        );
+    }
+
+    return text;
+}
@@ -0,0 +1,239 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::fs::{File, Metadata};
+use std::io::Read;
+use std::ops::Deref;
+
+use chrono::{DateTime, Local};
+use glob::glob;
+use memchr::memchr_iter;
+use memmap2::Mmap;
+use regex::Regex;
+
+use crate::analyse::LogSource;
+
+/// Where the logs to analyse are read from.
+pub trait InputSource {
+    /// Appends a [`LogSource`] for every file or stream the logs are read
+    /// from, the lines are split and filtered while they are parsed.
+    fn read_logs(&self, log_sources: &mut Vec<LogSource>) -> Result<(), String>;
+}
+
+/// Lines to analyse, a line is kept when it matches any of the `include`
+/// patterns, or when there are none, and matches none of the `exclude` ones.
+pub struct LineFilter<'a> {
+    pub include: &'a [Regex],
+    pub exclude: &'a [Regex],
+}
+
+impl LineFilter<'_> {
+    pub fn keeps(&self, line: &str) -> bool {
+        return (self.include.is_empty() || self.include.iter().any(|re| re.is_match(line)))
+            && !self.exclude.iter().any(|re| re.is_match(line));
+    }
+
+    /// Same as [`LineFilter::keeps`] for a line that may not be valid UTF-8,
+    /// it is only decoded when there are patterns to match.
+    pub fn keeps_bytes(&self, line: &[u8]) -> bool {
+        if self.include.is_empty() && self.exclude.is_empty() {
+            return true;
+        }
+        return self.keeps(&String::from_utf8_lossy(line));
+    }
+}
+
+/// Contents of a log file or stream.
+pub enum LogBytes {
+    /// A file mapped into memory, its pages are only read as the lines are
+    /// parsed, see [`GlobSource::mmap()`]
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for LogBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        return match self {
+            LogBytes::Mapped(mmap) => mmap,
+            LogBytes::Owned(bytes) => bytes,
+        };
+    }
+}
+
+/// Lines of `bytes` without their line endings, split the same way as
+/// [`str::lines`].
+pub fn byte_lines(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut line_start = 0;
+    let line_ends = memchr_iter(b'\n', bytes).chain(std::iter::once(bytes.len()));
+    return line_ends.filter_map(move |line_end| {
+        if line_start >= bytes.len() {
+            return None;
+        }
+        let line = &bytes[line_start..line_end];
+        line_start = line_end + 1;
+        if line_end == bytes.len() {
+            // The last line has no line ending
+            return Some(line);
+        }
+        return Some(line.strip_suffix(b"\r").unwrap_or(line));
+    });
+}
+
+/// Files matching any of the glob patterns, each file is read once even when
+/// several patterns match it.
+pub struct GlobSource {
+    pub patterns: Vec<String>,
+    /// Map the files into memory instead of reading them
+    pub mmap: bool,
+}
+
+impl GlobSource {
+    pub fn new(patterns: Vec<String>) -> GlobSource {
+        return GlobSource {
+            patterns,
+            mmap: false,
+        };
+    }
+
+    /// Maps the files into memory instead of reading them, which saves
+    /// copying them and holding them all in memory at once. A file truncated
+    /// while it is analysed, eg: by a `copytruncate` log rotation, then kills
+    /// the process with SIGBUS, so only map files that are not rotated
+    /// during the run.
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        return self;
+    }
+}
+
+impl InputSource for GlobSource {
+    fn read_logs(&self, log_sources: &mut Vec<LogSource>) -> Result<(), String> {
+        let mut file_paths: Vec<String> = Vec::new();
+        for file_pattern in &self.patterns {
+            let entries = glob(file_pattern)
+                .map_err(|err| format!("Invalid file pattern {}: {}", file_pattern, err))?;
+            for entry in entries {
+                let path = entry.map_err(|err| err.to_string())?;
+                file_paths.push(path.to_string_lossy().into_owned());
+            }
+        }
+        // Overlapping patterns should not count a file twice
+        file_paths.sort();
+        file_paths.dedup();
+
+        for path in file_paths {
+            let (bytes, metadata) = read_file(&path, self.mmap)
+                .map_err(|err| format!("Error reading log file {}: {}", path, err))?;
+            log_sources.push(LogSource {
+                name: path,
+                bytes,
+                file_id: file_id(&metadata),
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .map(|modified| DateTime::<Local>::from(modified).naive_local()),
+            });
+        }
+
+        return Ok(());
+    }
+}
+
+fn read_file(path: &str, mmap: bool) -> std::io::Result<(LogBytes, Metadata)> {
+    let mut file = File::open(path)?;
+    let metadata = file.metadata()?;
+    if mmap {
+        // SAFETY: the mapping is only read, but another process can still
+        // change the file under it. Bytes appended after the file was mapped
+        // are past the end of the mapping and never read. Bytes rewritten in
+        // place are seen changing under a `&[u8]`, and a file truncated while
+        // it is mapped, as `copytruncate` rotations do, raises SIGBUS on the
+        // next read of a page past its new end. Nothing here can prevent
+        // either, which is why mapping is only done when asked for.
+        if let Ok(mapped) = unsafe { Mmap::map(&file) } {
+            return Ok((LogBytes::Mapped(mapped), metadata));
+        }
+        // Pipes and other special files can not be mapped, they are read
+    }
+    let mut bytes: Vec<u8> = Vec::with_capacity(metadata.len() as usize);
+    file.read_to_end(&mut bytes)?;
+    return Ok((LogBytes::Owned(bytes), metadata));
+}
+
+/// Inode of a file, to tell a file rotated in place of another one apart
+#[cfg(unix)]
+pub(crate) fn file_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    return Some(metadata.ino());
+}
+
+// Without inodes a rotation is only noticed when the new file is shorter
+#[cfg(not(unix))]
+pub(crate) fn file_id(_metadata: &Metadata) -> Option<u64> {
+    return None;
+}
+
+/// Logs already held in memory, eg: read from stdin or a network stream.
+pub struct TextSource {
+    /// Name the logs are reported under
+    pub name: String,
+    pub text: String,
+}
+
+impl InputSource for TextSource {
+    fn read_logs(&self, log_sources: &mut Vec<LogSource>) -> Result<(), String> {
+        log_sources.push(LogSource {
+            name: self.name.clone(),
+            bytes: LogBytes::Owned(self.text.clone().into_bytes()),
+            file_id: None,
+            modified: None,
+        });
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(bytes: &[u8]) -> Vec<&[u8]> {
+        return byte_lines(bytes).collect();
+    }
+
+    #[test]
+    fn splits_lines_like_str_lines() {
+        for text in [
+            "",
+            "\n",
+            "one",
+            "one\n",
+            "one\ntwo",
+            "one\r\ntwo\r\n",
+            "one\r\ntwo",
+            "one\n\n\nfour\n",
+            "\r\n\r\n",
+            "cr in\rthe middle\n",
+            "ends with a cr\r",
+            "one\n\r",
+        ] {
+            let expected: Vec<&[u8]> = text.lines().map(str::as_bytes).collect();
+            assert_eq!(lines(text.as_bytes()), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn keeps_invalid_utf8() {
+        let bytes = b"2024-03-01 caf\xe9\r\n\xff\xfe\n\nlast \xc3";
+        assert_eq!(
+            lines(bytes),
+            vec![
+                &b"2024-03-01 caf\xe9"[..],
+                &b"\xff\xfe"[..],
+                &b""[..],
+                &b"last \xc3"[..],
+            ]
+        );
+    }
+}
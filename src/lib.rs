@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Aggregates log lines into counts of every loglevel per time bucket, and
+//! analyses and reports on them.
+//!
+//! An [`AnalysisConfig`] describes how the logs are parsed, an [`InputSource`]
+//! gives the log lines and the resulting [`Analysis`] is handed to the
+//! renderers in [`output`] and [`report`], which implement
+//! [`output::OutputRenderer`].
+//!
+//! ```no_run
+//! use loggregate::{analyse::loglevel::LogLevel, AnalysisConfig, GlobSource};
+//! use loggregate::output::json::generate_json_report;
+//!
+//! let config = AnalysisConfig::builder("%Y-%m-%d %H:%M:%S")
+//!     .gap_threshold(chrono::TimeDelta::minutes(10))
+//!     .build()?;
+//! let analysis = config.analyse(&GlobSource::new(vec!["/var/log/*.log".to_owned()]))?;
+//!
+//! println!("{} errors", analysis.combined_loglevel_count[&LogLevel::Error]);
+//! generate_json_report("report", &analysis);
+//! # Ok::<(), String>(())
+//! ```
+
+#![allow(clippy::needless_return)]
+
+/// Parsing of the log lines and the analyses run over them
+pub mod analyse;
+/// Configuration and result of an analysis
+pub mod analysis;
+/// Temporary and report directories
+pub mod fileops;
+/// Live dashboard of the logs being written
+pub mod follow;
+/// Sources of log lines
+pub mod input;
+/// Renderers of the json, csv, parquet, markdown and text reports
+pub mod output;
+mod parallel;
+/// Plots of the html report
+pub mod plot;
+/// Renderer of the html report
+pub mod report;
+/// Saved state of incremental analyses
+pub mod state;
+/// Sparklines, bars and colours for the terminal
+pub mod term;
+
+pub use analysis::{Analysis, AnalysisConfig, AnalysisConfigBuilder, AnalysisMetadata};
+pub use input::{GlobSource, InputSource, LineFilter, TextSource};
+pub use state::AnalysisState;
@@ -1,29 +1,50 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use clap::Parser;
+#![allow(clippy::needless_return)]
+
+use clap::{CommandFactory, FromArgMatches};
 use core::panic;
-use fileops::{
-    copy_reports_to_destination, prepare_plots_gen_dir, prepare_report_destination_dir,
-    prepare_tmp_loggregate_dir,
+use loggregate::analyse::rules::load_rules;
+use loggregate::analyse::threshold::evaluate_thresholds;
+use loggregate::fileops::{
+    copy_reports_to_destination, prepare_report_destination_dir, prepare_tmp_loggregate_dir,
 };
-use plot::plot_combined_bar_chart;
-use report::generate_html_report;
+use loggregate::follow::{follow_logs, FollowOptions};
+use loggregate::output::csv::CsvRenderer;
+use loggregate::output::json::JsonRenderer;
+use loggregate::output::markdown::MarkdownRenderer;
+#[cfg(feature = "parquet")]
+use loggregate::output::parquet::ParquetRenderer;
+use loggregate::output::text::TextRenderer;
+use loggregate::output::OutputRenderer;
+use loggregate::plot::PlotOptions;
+use loggregate::report::{load_template, HtmlRenderer, HtmlReportOptions};
+use loggregate::term::use_colors;
+use loggregate::{AnalysisConfig, AnalysisState, GlobSource};
+use std::process::exit;
 
-mod analyse;
 mod cli;
-mod fileops;
-mod plot;
-mod report;
+mod config;
 
-use crate::analyse::analyse_logs;
-use crate::analyse::calc::sum_of_log_occ;
-use crate::cli::{validate_input, Cli};
-use crate::fileops::read_logs;
-use crate::plot::plot_histograms;
+use crate::cli::{validate_input, Cli, OutputFormat};
+use crate::config::{apply_config, load_config};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let input = Cli::parse();
-    let mut log_lines: Vec<String> = Vec::new();
+    let matches = Cli::command().get_matches();
+    let mut input = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let config = match load_config(input.config.as_deref(), input.profile.as_deref()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
+    if let Some(config) = config {
+        if let Err(err) = apply_config(&mut input, &matches, config) {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    }
 
     match validate_input(&input) {
         Ok(_) => {}
@@ -32,36 +53,218 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    println!("Reading the log files...");
-    let no_of_files = read_logs(&input.glob_pattern, &mut log_lines);
-    let no_of_logs = log_lines.len() as i32;
-    println!("Completed reading the files");
+    if input.follow {
+        follow_logs(FollowOptions {
+            glob_patterns: input.glob_patterns,
+            include: input.include,
+            exclude: input.exclude,
+            parser: input.parser,
+            datetime_format: input.datetime_str_format,
+            syslog_year: input.syslog_year,
+            interval: input.follow_interval,
+            bucket_width: input.follow_bucket,
+            window: input.follow_window,
+        });
+        return Ok(());
+    }
+
+    let rules = match &input.rules {
+        Some(rules_path) => match load_rules(rules_path) {
+            Ok(rules) => rules,
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let template = match &input.template {
+        Some(template_path) => match load_template(template_path) {
+            Ok(template) => Some(template),
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut formats: Vec<OutputFormat> = Vec::new();
+    for format in &input.format {
+        if !formats.contains(format) {
+            formats.push(*format);
+        }
+    }
+    let renderers: Vec<Box<dyn OutputRenderer>> = formats
+        .into_iter()
+        .map(|format| make_renderer(format, &input, template.as_deref()))
+        .collect();
+
+    let mut config_builder = AnalysisConfig::builder(&input.datetime_str_format)
+        .parser(input.parser)
+        .silence_ratio(input.silence_ratio)
+        .rules(rules)
+        .message_patterns(
+            renderers
+                .iter()
+                .any(|renderer| renderer.shows_message_patterns()),
+        );
+    if let Some(jobs) = input.jobs {
+        config_builder = config_builder.jobs(jobs);
+    }
+    if let Some(syslog_year) = input.syslog_year {
+        config_builder = config_builder.syslog_year(syslog_year);
+    }
+    if let Some(gap_threshold) = input.gap_threshold {
+        config_builder = config_builder.gap_threshold(gap_threshold);
+    }
+    for pattern in input.include.iter().cloned() {
+        config_builder = config_builder.include(pattern);
+    }
+    for pattern in input.exclude.iter().cloned() {
+        config_builder = config_builder.exclude(pattern);
+    }
+    let analysis_config = config_builder.build()?;
+
+    println!("Reading and analysing the logs...");
+    let glob_source = GlobSource::new(input.glob_patterns.clone()).mmap(input.mmap);
+    let analysis = match &input.state {
+        Some(state_path) => {
+            let analysed = AnalysisState::load(state_path)
+                .and_then(|state| analysis_config.analyse_incremental(&glob_source, state));
+            match analysed {
+                Ok((analysis, state)) => {
+                    if let Err(err) = state.save(state_path) {
+                        eprintln!("{}", err);
+                        exit(1);
+                    }
+                    analysis
+                }
+                Err(err) => {
+                    eprintln!("{}", err);
+                    exit(1);
+                }
+            }
+        }
+        None => match analysis_config.analyse(&glob_source) {
+            Ok(analysis) => analysis,
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        },
+    };
+    let logs_analysis = &analysis.logs_analysis;
+
+    let parse_failures = &logs_analysis.parse_failures;
+    if parse_failures.total() > 0 {
+        eprintln!(
+            "{} of {} lines could not be parsed: {} without a datetime, {} with an invalid datetime, {} malformed records",
+            parse_failures.total(),
+            analysis.metadata.lines_read,
+            parse_failures.no_datetime_match,
+            parse_failures.datetime_parse_error,
+            parse_failures.malformed_record
+        );
+    }
+    if parse_failures.level_not_found > 0 {
+        eprintln!(
+            "{} lines without a known log level are counted as Others",
+            parse_failures.level_not_found
+        );
+    }
+    if let Some(rejects_path) = &input.rejects {
+        if let Err(err) = analysis.write_rejects(rejects_path) {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    }
+    if let Some(max_errors) = input.max_errors {
+        if parse_failures.total() > max_errors {
+            eprintln!(
+                "More lines than the {} allowed by --max-errors could not be parsed",
+                max_errors
+            );
+            exit(1);
+        }
+    }
 
-    println!("Analysing the logs..");
-    let logs_aggregate = analyse_logs(&log_lines, &input.datetime_str_format);
+    if let Some(rule_firings) = &logs_analysis.rule_firings {
+        println!("Rules fired {} times", rule_firings.len());
+    }
 
     let tmp_loggregate_dir = prepare_tmp_loggregate_dir();
-    let plots_gen_dir = prepare_plots_gen_dir(&tmp_loggregate_dir);
+    for renderer in &renderers {
+        if renderer.writes_files() {
+            println!("Preparing the {} report...", renderer.name());
+        }
+        renderer.render(&tmp_loggregate_dir, &analysis);
+    }
 
-    println!("Preparing the plots...");
-    plot_histograms(&plots_gen_dir, &logs_aggregate);
+    if renderers.iter().any(|renderer| renderer.writes_files()) {
+        println!("Copying report to the current directory...");
+        let report_destination_dir = prepare_report_destination_dir(&input.output_dir);
+        copy_reports_to_destination(&tmp_loggregate_dir, &report_destination_dir);
 
-    let combined_loglevel_count = sum_of_log_occ(&logs_aggregate.aggregates);
-    plot_combined_bar_chart(&plots_gen_dir, &combined_loglevel_count);
+        println!(
+            "Report is now available in '{}' directory",
+            input.output_dir
+        );
+    }
 
-    println!("Preparing the report...");
-    generate_html_report(
-        &tmp_loggregate_dir,
-        no_of_files,
-        no_of_logs,
-        &combined_loglevel_count,
-        input.user,
+    let violations = evaluate_thresholds(
+        &input.fail_on,
+        &logs_analysis.logs_aggregate,
+        &analysis.combined_loglevel_count,
     );
+    if !violations.is_empty() {
+        eprintln!(
+            "{} of {} --fail-on rules failed:",
+            violations.len(),
+            input.fail_on.len()
+        );
+        for violation in &violations {
+            eprintln!("  {}", violation);
+        }
+        exit(1);
+    }
 
-    println!("Copying report to the current directory...");
-    let report_destination_dir = prepare_report_destination_dir();
-    copy_reports_to_destination(&tmp_loggregate_dir, &report_destination_dir);
-
-    println!("Report is now available in 'report' directory");
     Ok(())
 }
+
+fn make_renderer(
+    format: OutputFormat,
+    input: &Cli,
+    template: Option<&str>,
+) -> Box<dyn OutputRenderer> {
+    return match format {
+        OutputFormat::Html => Box::new(HtmlRenderer {
+            options: HtmlReportOptions {
+                user: input.user.clone(),
+                self_contained: input.self_contained,
+                interactive: input.interactive,
+                y_scale: input.y_scale,
+                plot_options: PlotOptions {
+                    format: input.plot_format,
+                    width: input.plot_size.0,
+                    height: input.plot_size.1,
+                    dpi: input.plot_dpi,
+                },
+                template: template.map(str::to_owned),
+            },
+        }),
+        OutputFormat::Json => Box::new(JsonRenderer),
+        OutputFormat::Csv => Box::new(CsvRenderer),
+        #[cfg(feature = "parquet")]
+        OutputFormat::Parquet => Box::new(ParquetRenderer),
+        OutputFormat::Text => Box::new(TextRenderer {
+            colors: use_colors(),
+        }),
+        OutputFormat::Markdown => Box::new(MarkdownRenderer {
+            // Self contained html reports leave no plots to link to
+            plot_extension: (input.format.contains(&OutputFormat::Html) && !input.self_contained)
+                .then(|| input.plot_format.extension()),
+        }),
+    };
+}
@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::analysis::Analysis;
+
+pub mod csv;
+pub mod json;
+pub mod markdown;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod text;
+
+/// Turns an analysis into a report of one format.
+pub trait OutputRenderer {
+    /// Name of the format, eg: "html"
+    fn name(&self) -> &'static str;
+
+    /// Writes the report into `report_dir`, or prints it when the renderer
+    /// does not write files.
+    fn render(&self, report_dir: &str, analysis: &Analysis);
+
+    /// Whether the report is written into the report directory
+    fn writes_files(&self) -> bool {
+        return true;
+    }
+
+    /// Whether the report lists the most frequent message patterns, they are
+    /// only counted when a report shows them
+    fn shows_message_patterns(&self) -> bool {
+        return true;
+    }
+}
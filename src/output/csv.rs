@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::analyse::{
+    loglevel::{LogLevel, LOGLEVELS},
+    LogsAggregate,
+};
+use crate::analysis::Analysis;
+use crate::output::OutputRenderer;
+
+const CSV_DATETIME_FMT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Writes `report.csv` with the counts of every bucket.
+pub struct CsvRenderer;
+
+impl OutputRenderer for CsvRenderer {
+    fn name(&self) -> &'static str {
+        return "csv";
+    }
+
+    fn render(&self, report_dir: &str, analysis: &Analysis) {
+        generate_csv_report(report_dir, analysis);
+    }
+
+    fn shows_message_patterns(&self) -> bool {
+        return false;
+    }
+}
+
+pub fn generate_csv_report(report_dir: &str, analysis: &Analysis) {
+    let csv_report_path = report_dir.to_string() + "/report.csv";
+    let file = File::create(csv_report_path).unwrap();
+    let mut writer = BufWriter::new(file);
+
+    write_csv_table(&mut writer, &analysis.logs_analysis.logs_aggregate).unwrap();
+}
+
+/// Writes one row per bucket, the first column being the start of the bucket
+/// and then one column per loglevel in the order of severity.
+pub fn write_csv_table(
+    writer: &mut impl Write,
+    logs_aggregate: &LogsAggregate,
+) -> std::io::Result<()> {
+    let header: Vec<String> = LOGLEVELS.iter().map(loglevel_column_name).collect();
+    writeln!(writer, "datetime,{}", header.join(","))?;
+
+    for (idx, datetime) in logs_aggregate.datetimes.iter().enumerate() {
+        let counts: Vec<String> = LOGLEVELS
+            .iter()
+            .map(|loglevel| format!("{}", logs_aggregate.aggregates[loglevel][idx]))
+            .collect();
+        writeln!(
+            writer,
+            "{},{}",
+            datetime.format(CSV_DATETIME_FMT),
+            counts.join(",")
+        )?;
+    }
+
+    return writer.flush();
+}
+
+pub fn loglevel_column_name(loglevel: &LogLevel) -> String {
+    return format!("{:?}", loglevel).to_lowercase();
+}
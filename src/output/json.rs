@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{collections::BTreeMap, fs::File, io::Write};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::analyse::{
+    dtfmt::DateTimeCat, gaps::GapAnalysis, loglevel::LogLevel, metrics::LogMetrics,
+    patterns::MessagePattern, rules::RuleFiring, ParseFailures,
+};
+use crate::analysis::{Analysis, AnalysisMetadata};
+use crate::output::OutputRenderer;
+
+/// Version of the JSON document layout. Bump it whenever a field is renamed,
+/// removed or changes meaning, adding new fields does not need a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    schema_version: u32,
+    /// Deprecated, the same as the generator and version of `metadata`. Kept
+    /// until the next schema version for the scripts already reading it.
+    generator: Generator,
+    granularity: &'a DateTimeCat,
+    buckets: &'a [NaiveDateTime],
+    series: BTreeMap<LogLevel, &'a Vec<i32>>,
+    totals: BTreeMap<LogLevel, i32>,
+    no_of_files: i32,
+    no_of_logs: i32,
+    parse_failures: &'a ParseFailures,
+    metrics: &'a LogMetrics,
+    gaps: &'a GapAnalysis,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_violations: Option<&'a Vec<RuleFiring>>,
+    message_patterns: &'a [MessagePattern],
+    metadata: &'a AnalysisMetadata,
+}
+
+#[derive(Serialize)]
+struct Generator {
+    name: &'static str,
+    version: &'static str,
+}
+
+/// Writes `report.json`, see [`SCHEMA_VERSION`] for the compatibility of its layout.
+pub struct JsonRenderer;
+
+impl OutputRenderer for JsonRenderer {
+    fn name(&self) -> &'static str {
+        return "json";
+    }
+
+    fn render(&self, report_dir: &str, analysis: &Analysis) {
+        generate_json_report(report_dir, analysis);
+    }
+}
+
+pub fn generate_json_report(report_dir: &str, analysis: &Analysis) {
+    let json_report_path = report_dir.to_string() + "/report.json";
+    let logs_analysis = &analysis.logs_analysis;
+    let logs_aggregate = &logs_analysis.logs_aggregate;
+
+    let json_report = JsonReport {
+        schema_version: SCHEMA_VERSION,
+        generator: Generator {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+        },
+        granularity: &logs_aggregate.datetime_cat,
+        buckets: &logs_aggregate.datetimes,
+        series: logs_aggregate
+            .aggregates
+            .iter()
+            .map(|(k, v)| (*k, v))
+            .collect(),
+        totals: analysis
+            .combined_loglevel_count
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect(),
+        no_of_files: analysis.no_of_files,
+        no_of_logs: analysis.no_of_logs,
+        parse_failures: &logs_analysis.parse_failures,
+        metrics: &logs_analysis.metrics,
+        gaps: &logs_analysis.gaps,
+        rule_violations: logs_analysis.rule_firings.as_ref(),
+        message_patterns: &logs_analysis.message_patterns,
+        metadata: &analysis.metadata,
+    };
+
+    let file = File::create(json_report_path).unwrap();
+    let mut writer = std::io::BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, &json_report).unwrap();
+    writer.write_all(b"\n").unwrap();
+}
@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{fs::File, io::Write};
+
+use crate::analyse::{
+    dtfmt::{format_duration, get_dt_fmt, GENERATED_AT_FMT, TIME_SPAN_FMT},
+    gaps::GapKind,
+    loglevel::LOGLEVELS,
+};
+use crate::analysis::Analysis;
+use crate::output::OutputRenderer;
+use crate::term::{downsample, sparkline};
+
+const SPARKLINE_WIDTH: usize = 40;
+const PLOTS: [(&str, &str); 4] = [
+    ("timeline", "Logs over time"),
+    ("cumulative", "Cumulative counts"),
+    ("combined", "Logs per level"),
+    ("heatmap_level_time", "Levels over time"),
+];
+
+/// Writes `report.md`, a summary short enough to be posted as a comment on
+/// a pull request or an issue.
+pub struct MarkdownRenderer {
+    /// Extension of the plots of the html report written along, the report
+    /// links to them when given
+    pub plot_extension: Option<&'static str>,
+}
+
+impl OutputRenderer for MarkdownRenderer {
+    fn name(&self) -> &'static str {
+        return "markdown";
+    }
+
+    fn render(&self, report_dir: &str, analysis: &Analysis) {
+        let markdown_report_path = report_dir.to_string() + "/report.md";
+        let mut file = File::create(markdown_report_path).unwrap();
+        file.write_all(generate_markdown_report(analysis, self.plot_extension).as_bytes())
+            .unwrap();
+    }
+}
+
+pub fn generate_markdown_report(analysis: &Analysis, plot_extension: Option<&str>) -> String {
+    let logs_analysis = &analysis.logs_analysis;
+    let logs_aggregate = &logs_analysis.logs_aggregate;
+    let combined_loglevel_count = &analysis.combined_loglevel_count;
+    let dt_fmt = get_dt_fmt(&logs_aggregate.datetime_cat);
+    let total: i32 = combined_loglevel_count.values().sum();
+
+    let mut text = String::from("## Loggregate report\n\n");
+    text += &format!(
+        "**{}** logs in **{}** files",
+        analysis.no_of_logs, analysis.no_of_files
+    );
+    if let (Some(first), Some(last)) = (
+        logs_aggregate.datetimes.first(),
+        logs_aggregate.datetimes.last(),
+    ) {
+        text += &format!(
+            " from {} to {}, grouped in {} buckets ({:?})",
+            first.format(&dt_fmt),
+            last.format(&dt_fmt),
+            logs_aggregate.datetimes.len(),
+            logs_aggregate.datetime_cat
+        );
+    }
+    text += &format!(
+        ", **{:.2}%** of the lines parsed.\n\n",
+        analysis.metadata.coverage
+    );
+
+    // Same counts as the summary of the html report
+    let names: Vec<String> = LOGLEVELS
+        .iter()
+        .map(|loglevel| format!("{:?}", loglevel))
+        .collect();
+    let counts: Vec<String> = LOGLEVELS
+        .iter()
+        .map(|loglevel| combined_loglevel_count[loglevel].to_string())
+        .collect();
+    text += &format!(
+        "| Files | Logs | {} |\n|{}\n| {} | {} | {} |\n\n",
+        names.join(" | "),
+        "--:|".repeat(LOGLEVELS.len() + 2),
+        analysis.no_of_files,
+        analysis.no_of_logs,
+        counts.join(" | ")
+    );
+
+    if let Some(rule_firings) = &logs_analysis.rule_firings {
+        if rule_firings.is_empty() {
+            text += "None of the alert rules fired.\n\n";
+        } else {
+            text += &format!("### Rule violations ({})\n\n", rule_firings.len());
+            text +=
+                "| Rule | Severity | Condition | From | To | Value |\n|:--|:--|:--|:--|:--|--:|\n";
+            for firing in rule_firings {
+                text += &format!(
+                    "| {} | {:?} | `{}` | {} | {} | {} |\n",
+                    escape_cell(&firing.rule),
+                    firing.severity,
+                    escape_code(&firing.condition),
+                    firing.start.format(&dt_fmt),
+                    firing.end.format(&dt_fmt),
+                    firing.value_text()
+                );
+            }
+            text += "\n";
+        }
+    }
+
+    text += "### Log levels\n\n| Level | Count | Share | Trend |\n|:--|--:|--:|:--|\n";
+    for loglevel in LOGLEVELS.iter() {
+        let count = combined_loglevel_count[loglevel];
+        let share = if total > 0 {
+            count as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+        let trend = if count > 0 {
+            let counts = downsample(&logs_aggregate.aggregates[loglevel], SPARKLINE_WIDTH);
+            format!("`{}`", sparkline(&counts))
+        } else {
+            String::new()
+        };
+        text += &format!(
+            "| {:?} | {} | {:.1}% | {} |\n",
+            loglevel, count, share, trend
+        );
+    }
+    text += &format!("| **Total** | **{}** | | |\n\n", total);
+
+    let metrics = &logs_analysis.metrics;
+    text += &format!(
+        "Error ratio: **{:.2}%**, errors per minute: **{:.2}**, peak errors per minute: **{:.2}**.\n\n",
+        metrics.error_ratio * 100.0,
+        metrics.errors_per_minute,
+        metrics.peak_errors_per_minute
+    );
+
+    if !logs_analysis.message_patterns.is_empty() {
+        text += "### Top message patterns\n\n| Count | Level | Pattern |\n|--:|:--|:--|\n";
+        for message_pattern in &logs_analysis.message_patterns {
+            text += &format!(
+                "| {} | {:?} | `{}` |\n",
+                message_pattern.count,
+                message_pattern.loglevel,
+                escape_code(&message_pattern.pattern)
+            );
+        }
+        text += "\n";
+    }
+
+    let gaps = &logs_analysis.gaps.gaps;
+    if !gaps.is_empty() {
+        // Collapsed so that a long list does not take over the comment
+        text += &format!(
+            "<details><summary>Gaps in the logs ({})</summary>\n\n| Source | Kind | From | To | Duration |\n|:--|:--|:--|:--|--:|\n",
+            gaps.len()
+        );
+        for gap in gaps {
+            text += &format!(
+                "| {} | {} | {} | {} | {} |\n",
+                escape_cell(&gap.source),
+                match gap.kind {
+                    GapKind::NoLogs => "No logs",
+                    GapKind::LowVolume => "Low volume",
+                },
+                gap.start.format(&dt_fmt),
+                gap.end.format(&dt_fmt),
+                format_duration(gap.duration_secs)
+            );
+        }
+        text += "\n</details>\n\n";
+    }
+
+    if let Some(plot_extension) = plot_extension {
+        text += "### Charts\n\n";
+        for (name, title) in PLOTS {
+            text += &format!("![{}](plots/{}.{})\n", title, name, plot_extension);
+        }
+        text += "\nSee `report.html` for the full report.\n\n";
+    }
+
+    text += &metadata_section(analysis);
+
+    let skipped = logs_analysis.parse_failures.total();
+    text += &format!(
+        "<sub>Generated by {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    if skipped > 0 {
+        text += &format!(", {} lines could not be parsed", skipped);
+    }
+    text += "</sub>\n";
+
+    return text;
+}
+
+// Collapsed as it is only needed to check or reproduce the report
+fn metadata_section(analysis: &Analysis) -> String {
+    let metadata = &analysis.metadata;
+    let code_list = |items: &[String]| {
+        items
+            .iter()
+            .map(|item| format!("`{}`", escape_code(item)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+
+    let mut rows: Vec<(&str, String)> = vec![
+        (
+            "Generated",
+            format!(
+                "{} by {} {}",
+                metadata.generated_at.format(GENERATED_AT_FMT),
+                metadata.generator,
+                metadata.version
+            ),
+        ),
+        ("Inputs", code_list(&metadata.inputs)),
+        ("Parser", metadata.parser_name()),
+    ];
+    if !metadata.datetime_format.is_empty() {
+        rows.push((
+            "Datetime format",
+            format!("`{}`", escape_code(&metadata.datetime_format)),
+        ));
+    }
+    if !metadata.include.is_empty() {
+        rows.push(("Include", code_list(&metadata.include)));
+    }
+    if !metadata.exclude.is_empty() {
+        rows.push(("Exclude", code_list(&metadata.exclude)));
+    }
+    if let (Some(first_log), Some(last_log)) = (metadata.first_log, metadata.last_log) {
+        rows.push((
+            "Time span",
+            format!(
+                "{} to {}",
+                first_log.format(TIME_SPAN_FMT),
+                last_log.format(TIME_SPAN_FMT)
+            ),
+        ));
+    }
+    rows.push(("Time buckets", format!("{:?}", metadata.granularity)));
+    rows.push((
+        "Lines",
+        format!(
+            "{} read, {} not parsed ({:.2}% coverage)",
+            metadata.lines_read, metadata.lines_unparsed, metadata.coverage
+        ),
+    ));
+
+    let mut text = String::from("<details><summary>Metadata</summary>\n\n| | |\n|:--|:--|\n");
+    for (name, value) in rows {
+        text += &format!("| {} | {} |\n", name, value);
+    }
+    text += "\n</details>\n\n";
+
+    return text;
+}
+
+// Pipes would end the table cell even in a code span
+fn escape_cell(text: &str) -> String {
+    return text.replace('|', "\\|");
+}
+
+fn escape_code(text: &str) -> String {
+    return escape_cell(&text.replace('`', "'"));
+}
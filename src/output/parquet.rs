@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{fs::File, sync::Arc};
+
+use parquet::{
+    basic::Compression,
+    data_type::{Int32Type, Int64Type},
+    errors::ParquetError,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+
+use crate::analyse::{loglevel::LOGLEVELS, LogsAggregate};
+use crate::analysis::Analysis;
+use crate::output::OutputRenderer;
+
+use super::csv::loglevel_column_name;
+
+/// Writes `report.parquet` with the same table as the csv report.
+pub struct ParquetRenderer;
+
+impl OutputRenderer for ParquetRenderer {
+    fn name(&self) -> &'static str {
+        return "parquet";
+    }
+
+    fn render(&self, report_dir: &str, analysis: &Analysis) {
+        generate_parquet_report(report_dir, analysis);
+    }
+
+    fn shows_message_patterns(&self) -> bool {
+        return false;
+    }
+}
+
+pub fn generate_parquet_report(report_dir: &str, analysis: &Analysis) {
+    let parquet_report_path = report_dir.to_string() + "/report.parquet";
+    let file = File::create(parquet_report_path).unwrap();
+
+    write_parquet_table(file, &analysis.logs_analysis.logs_aggregate)
+        .expect("Error while writing parquet report");
+}
+
+/// Same table as the csv report, the datetime column is stored as a
+/// timestamp in milliseconds and each loglevel as an INT32 column.
+fn write_parquet_table(file: File, logs_aggregate: &LogsAggregate) -> Result<(), ParquetError> {
+    let mut message_type =
+        String::from("message loggregate { REQUIRED INT64 datetime (TIMESTAMP(MILLIS, false));");
+    for loglevel in &LOGLEVELS {
+        message_type += &format!(" REQUIRED INT32 {};", loglevel_column_name(loglevel));
+    }
+    message_type += " }";
+
+    let schema = Arc::new(parse_message_type(&message_type)?);
+    let props = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+    let mut writer = SerializedFileWriter::new(file, schema, props)?;
+    let mut row_group = writer.next_row_group()?;
+
+    let datetimes: Vec<i64> = logs_aggregate
+        .datetimes
+        .iter()
+        .map(|dt| dt.and_utc().timestamp_millis())
+        .collect();
+    let mut column = row_group.next_column()?.unwrap();
+    column
+        .typed::<Int64Type>()
+        .write_batch(&datetimes, None, None)?;
+    column.close()?;
+
+    for loglevel in &LOGLEVELS {
+        let mut column = row_group.next_column()?.unwrap();
+        column.typed::<Int32Type>().write_batch(
+            &logs_aggregate.aggregates[loglevel],
+            None,
+            None,
+        )?;
+        column.close()?;
+    }
+
+    row_group.close()?;
+    writer.close()?;
+
+    return Ok(());
+}
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::analyse::{
+    dtfmt::get_dt_fmt,
+    loglevel::{LogLevel, LOGLEVELS},
+};
+use crate::analysis::Analysis;
+use crate::output::OutputRenderer;
+use crate::plot::get_color;
+use crate::term::{bar, colorize, downsample, sparkline};
+
+const BAR_WIDTH: usize = 40;
+const SPARKLINE_WIDTH: usize = 80;
+
+/// Prints the text report to the terminal instead of writing a file.
+pub struct TextRenderer {
+    pub colors: bool,
+}
+
+impl OutputRenderer for TextRenderer {
+    fn name(&self) -> &'static str {
+        return "text";
+    }
+
+    fn render(&self, _report_dir: &str, analysis: &Analysis) {
+        print!("\n{}", generate_text_report(analysis, self.colors));
+    }
+
+    fn writes_files(&self) -> bool {
+        return false;
+    }
+
+    fn shows_message_patterns(&self) -> bool {
+        return false;
+    }
+}
+
+/// Plain text report for the terminal, coloured when `colors` is set.
+pub fn generate_text_report(analysis: &Analysis, colors: bool) -> String {
+    let logs_aggregate = &analysis.logs_analysis.logs_aggregate;
+    let combined_loglevel_count = &analysis.combined_loglevel_count;
+    let dt_fmt = get_dt_fmt(&logs_aggregate.datetime_cat);
+    let total: i32 = combined_loglevel_count.values().sum();
+    let max_count = combined_loglevel_count.values().copied().max().unwrap_or(0);
+    let level_name = |loglevel: &LogLevel| {
+        let name = format!("{:<10}", format!("{:?}", loglevel));
+        colorize(&name, &get_color(loglevel), colors)
+    };
+
+    let mut text = String::new();
+    text += &format!(
+        "Files: {}  Logs: {}  Parsed: {:.2}%  Time buckets: {} ({:?})",
+        analysis.no_of_files,
+        analysis.no_of_logs,
+        analysis.metadata.coverage,
+        logs_aggregate.datetimes.len(),
+        logs_aggregate.datetime_cat
+    );
+    if let (Some(first), Some(last)) = (
+        logs_aggregate.datetimes.first(),
+        logs_aggregate.datetimes.last(),
+    ) {
+        text += &format!(
+            "  From: {}  To: {}",
+            first.format(&dt_fmt),
+            last.format(&dt_fmt)
+        );
+    }
+    text += "\n\n";
+
+    text += &format!("{:<10} {:>10} {:>7}\n", "Level", "Count", "Share");
+    for loglevel in LOGLEVELS.iter() {
+        let count = combined_loglevel_count[loglevel];
+        let share = if total > 0 {
+            count as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+        text += &format!(
+            "{} {:>10} {:>6.1}%  {}\n",
+            level_name(loglevel),
+            count,
+            share,
+            colorize(
+                &bar(count, max_count, BAR_WIDTH),
+                &get_color(loglevel),
+                colors
+            )
+        );
+    }
+    text += &format!("{:<10} {:>10}\n\n", "Total", total);
+
+    text += "Trends\n";
+    for loglevel in LOGLEVELS.iter() {
+        let counts = downsample(&logs_aggregate.aggregates[loglevel], SPARKLINE_WIDTH);
+        text += &format!(
+            "{} {}\n",
+            level_name(loglevel),
+            colorize(&sparkline(&counts), &get_color(loglevel), colors)
+        );
+    }
+    text += "\n";
+
+    let header: Vec<String> = LOGLEVELS
+        .iter()
+        .map(|loglevel| {
+            let name = format!("{:>9}", format!("{:?}", loglevel));
+            colorize(&name, &get_color(loglevel), colors)
+        })
+        .collect();
+    text += &format!("{:<19} {} {:>9}\n", "Datetime", header.join(" "), "Total");
+    for (idx, datetime) in logs_aggregate.datetimes.iter().enumerate() {
+        let counts: Vec<String> = LOGLEVELS
+            .iter()
+            .map(|loglevel| format!("{:>9}", logs_aggregate.aggregates[loglevel][idx]))
+            .collect();
+        let bucket_total: i32 = LOGLEVELS
+            .iter()
+            .map(|loglevel| logs_aggregate.aggregates[loglevel][idx])
+            .sum();
+        text += &format!(
+            "{:<19} {} {:>9}\n",
+            datetime.format(&dt_fmt).to_string(),
+            counts.join(" "),
+            bucket_total
+        );
+    }
+
+    return text;
+}
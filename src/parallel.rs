@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::thread;
+
+/// Number of workers used when none is given, one per CPU core.
+pub fn default_jobs() -> usize {
+    return thread::available_parallelism()
+        .map(|jobs| jobs.get())
+        .unwrap_or(1);
+}
+
+/// Splits `items` into up to `jobs` contiguous chunks and runs `work` over
+/// every chunk on its own thread, along with the index of the chunk's first
+/// item. The results are returned in the order of the chunks so that they
+/// can be merged as if the items had been gone through one by one.
+pub fn map_chunks<T, R, F>(items: &[T], jobs: usize, work: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &[T]) -> R + Sync,
+{
+    let chunk_size = items.len().div_ceil(jobs.max(1)).max(1);
+    if jobs <= 1 || items.len() <= chunk_size {
+        return vec![work(0, items)];
+    }
+
+    let work = &work;
+    return thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(idx, chunk)| scope.spawn(move || work(idx * chunk_size, chunk)))
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+}
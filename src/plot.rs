@@ -2,84 +2,225 @@
 
 use std::collections::HashMap;
 
+use clap::ValueEnum;
+
+use chrono::{Datelike, NaiveDateTime};
 use plotters::{
-    backend::BitMapBackend,
-    chart::{ChartBuilder, LabelAreaPosition},
-    coord::ranged1d::{IntoSegmentedCoord, SegmentValue},
-    drawing::IntoDrawingArea,
-    series::Histogram,
+    backend::{BitMapBackend, DrawingBackend, SVGBackend},
+    chart::{ChartBuilder, ChartContext, LabelAreaPosition, SeriesLabelPosition},
+    coord::{
+        cartesian::Cartesian2d,
+        combinators::IntoLogRange,
+        ranged1d::{IntoSegmentedCoord, Ranged, SegmentValue, ValueFormatter},
+        types::RangedCoordusize,
+        Shift,
+    },
+    drawing::{DrawingArea, IntoDrawingArea},
+    element::{PathElement, Rectangle, Text},
+    series::{Histogram, LineSeries},
     style::{
         full_palette::{
             BLUEGREY, GREY, LIGHTBLUE, LIGHTGREEN_A400, ORANGE_600, RED_400, RED_500, RED_600,
             RED_700,
         },
-        Color, FontTransform, IntoFont, RGBColor, ShapeStyle, TextStyle, WHITE, YELLOW,
+        text_anchor::{HPos, Pos, VPos},
+        Color, FontStyle, IntoFont, RGBColor, ShapeStyle, TextStyle, BLACK, WHITE, YELLOW,
     },
 };
 
 use crate::analyse::{
     calc::{max_log_count, max_log_occ},
-    dtfmt::{get_dt_fmt, DateTimeCat},
-    loglevel::LogLevel,
-    LogsAggregate,
+    dtfmt::{get_axis_dt_fmts, DateTimeCat},
+    gaps::Gap,
+    loglevel::{LogLevel, LOGLEVELS},
+    metrics::LogMetrics,
+    HourWeekdayCounts, LogsAggregate,
 };
 
-pub fn plot_histograms(plot_gen_dir: &str, logs_aggregate: &LogsAggregate) {
-    let num_dt = logs_aggregate.datetimes.len();
+const BASE_DPI: u32 = 96;
+const TIME_AXIS_LABEL_AREA: i32 = 50;
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const HEAT_BLUE: RGBColor = RGBColor(21, 101, 192);
+const GAP_GREY: RGBColor = RGBColor(158, 158, 158);
 
-    for (loglevel, _) in &logs_aggregate.aggregates {
-        let max_logs = max_log_occ(&logs_aggregate.aggregates, loglevel);
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum PlotFormat {
+    Png,
+    Svg,
+}
 
-        let plot_file_path =
-            format!("{}", plot_gen_dir) + "/" + &format!("{:?}", loglevel).to_lowercase() + ".png";
-        let root = BitMapBackend::new(&plot_file_path, (1280, 720)).into_drawing_area();
-        _ = root.fill(&WHITE);
-        let mut chart = ChartBuilder::on(&root)
-            .caption(format!("{:?}", loglevel), ("sans-serif", 50).into_font())
-            .margin(10)
-            .margin_bottom(110)
-            .x_label_area_size(30)
-            .y_label_area_size(30)
-            .build_cartesian_2d((0..num_dt - 1).into_segmented(), 0..max_logs)
-            .unwrap();
-
-        _ = chart
-            .configure_mesh()
-            .disable_x_mesh()
-            .x_label_offset(5)
-            .x_labels(num_dt)
-            .set_tick_mark_size(
-                LabelAreaPosition::Bottom,
-                get_tick_size(&logs_aggregate.datetime_cat),
-            )
-            .x_label_formatter(&|x| match x {
-                SegmentValue::CenterOf(v) => logs_aggregate.datetimes[*v as usize]
-                    .clone()
-                    .format(&get_dt_fmt(&logs_aggregate.datetime_cat))
-                    .to_string(),
-                _ => "UNK".to_string(),
-            })
-            .x_label_style(
-                TextStyle::from(("sans-serif", 12).into_font()).transform(FontTransform::Rotate90),
-            )
-            .draw();
+impl Default for PlotFormat {
+    // Without the ttf feature there are no fonts to rasterize png labels with
+    fn default() -> Self {
+        if cfg!(feature = "ttf") {
+            return PlotFormat::Png;
+        }
+        return PlotFormat::Svg;
+    }
+}
 
-        chart
-            .draw_series(
-                Histogram::vertical(&chart)
-                    .style(get_color_style(&loglevel))
-                    .data((0..num_dt).map(|x| (x, logs_aggregate.aggregates[loglevel][x]))),
-            )
-            .unwrap()
-            .label(format!("{:?}", loglevel));
+impl PlotFormat {
+    pub fn extension(&self) -> &'static str {
+        return match self {
+            PlotFormat::Png => "png",
+            PlotFormat::Svg => "svg",
+        };
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum YScale {
+    /// Number of logs
+    Linear,
+    /// Number of logs on a logarithmic axis
+    Log,
+    /// Share of each loglevel among all logs of the bucket
+    Percent,
+}
+
+pub struct PlotOptions {
+    pub format: PlotFormat,
+    pub width: u32,
+    pub height: u32,
+    pub dpi: u32,
+}
+
+impl PlotOptions {
+    pub fn extension(&self) -> &'static str {
+        return self.format.extension();
+    }
+
+    fn plot_file_path(&self, plot_gen_dir: &str, name: &str) -> String {
+        return format!("{}/{}.{}", plot_gen_dir, name, self.extension());
+    }
+
+    // Width and height are given at 96 DPI, PNGs rendered at a higher DPI
+    // get more pixels and every font size, margin etc. is scaled along.
+    fn scale(&self) -> f64 {
+        return match self.format {
+            PlotFormat::Png => self.dpi as f64 / BASE_DPI as f64,
+            PlotFormat::Svg => 1.0,
+        };
+    }
+
+    fn size(&self) -> (u32, u32) {
+        return (
+            (self.width as f64 * self.scale()).round() as u32,
+            (self.height as f64 * self.scale()).round() as u32,
+        );
+    }
 
-        _ = root.present();
+    fn px(&self, value: i32) -> i32 {
+        return (value as f64 * self.scale()).round() as i32;
     }
 }
 
+pub fn plot_histograms(
+    plot_gen_dir: &str,
+    logs_aggregate: &LogsAggregate,
+    plot_options: &PlotOptions,
+) {
+    for loglevel in logs_aggregate.aggregates.keys() {
+        let plot_file_path =
+            plot_options.plot_file_path(plot_gen_dir, &format!("{:?}", loglevel).to_lowercase());
+        let size = plot_options.size();
+
+        match plot_options.format {
+            PlotFormat::Png => draw_histogram(
+                BitMapBackend::new(&plot_file_path, size).into_drawing_area(),
+                logs_aggregate,
+                loglevel,
+                plot_options,
+            ),
+            PlotFormat::Svg => draw_histogram(
+                SVGBackend::new(&plot_file_path, size).into_drawing_area(),
+                logs_aggregate,
+                loglevel,
+                plot_options,
+            ),
+        }
+    }
+}
+
+fn draw_histogram<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    logs_aggregate: &LogsAggregate,
+    loglevel: &LogLevel,
+    plot_options: &PlotOptions,
+) {
+    let num_dt = logs_aggregate.datetimes.len();
+    let max_logs = max_log_occ(&logs_aggregate.aggregates, loglevel);
+
+    _ = root.fill(&WHITE);
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            format!("{:?}", loglevel),
+            ("sans-serif", plot_options.px(50)).into_font(),
+        )
+        .margin(plot_options.px(10))
+        .margin_bottom(plot_options.px(20))
+        .x_label_area_size(plot_options.px(TIME_AXIS_LABEL_AREA))
+        .y_label_area_size(plot_options.px(30))
+        .build_cartesian_2d(0..num_dt.max(1), 0..max_logs.max(1))
+        .unwrap();
+
+    _ = chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(1)
+        .x_label_formatter(&|_| String::new())
+        .set_tick_mark_size(LabelAreaPosition::Bottom, 0)
+        .y_label_style(("sans-serif", plot_options.px(12)))
+        .draw();
+    draw_time_axis(&root, &chart, logs_aggregate, plot_options);
+
+    // Gaps between the bars would hide the bars themselves once they get thin
+    let bar_width = chart.plotting_area().dim_in_pixel().0 as usize / num_dt.max(1);
+    let bar_margin = if bar_width > 10 {
+        plot_options.px(3)
+    } else {
+        0
+    };
+
+    chart
+        .draw_series(
+            Histogram::vertical(&chart)
+                .style(get_color_style(loglevel))
+                .margin(bar_margin as u32)
+                .data((0..num_dt).map(|x| (x, logs_aggregate.aggregates[loglevel][x]))),
+        )
+        .unwrap()
+        .label(format!("{:?}", loglevel));
+
+    _ = root.present();
+}
+
 pub fn plot_combined_bar_chart(
     plot_gen_dir: &str,
     combined_loglevel_count: &HashMap<LogLevel, i32>,
+    plot_options: &PlotOptions,
+) {
+    let plot_file_path = plot_options.plot_file_path(plot_gen_dir, "combined");
+    let size = plot_options.size();
+
+    match plot_options.format {
+        PlotFormat::Png => draw_combined_bar_chart(
+            BitMapBackend::new(&plot_file_path, size).into_drawing_area(),
+            combined_loglevel_count,
+            plot_options,
+        ),
+        PlotFormat::Svg => draw_combined_bar_chart(
+            SVGBackend::new(&plot_file_path, size).into_drawing_area(),
+            combined_loglevel_count,
+            plot_options,
+        ),
+    }
+}
+
+fn draw_combined_bar_chart<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    combined_loglevel_count: &HashMap<LogLevel, i32>,
+    plot_options: &PlotOptions,
 ) {
     let num_log_levels = combined_loglevel_count.len();
     let loglevels: Vec<String> = combined_loglevel_count
@@ -90,30 +231,34 @@ pub fn plot_combined_bar_chart(
         .values()
         .map(|x| x.to_owned())
         .collect();
-    let max_count = max_log_count(&combined_loglevel_count);
-    let plot_file_path = format!("{}", plot_gen_dir) + "/combined.png";
-    let root = BitMapBackend::new(&plot_file_path, (1280, 720)).into_drawing_area();
+    let max_count = max_log_count(combined_loglevel_count);
     _ = root.fill(&WHITE);
     let mut chart = ChartBuilder::on(&root)
-        .caption("Combined Loglevel Count", ("sans-serif", 50).into_font())
-        .margin(10)
-        .margin_bottom(70)
-        .x_label_area_size(30)
-        .y_label_area_size(30)
+        .caption(
+            "Combined Loglevel Count",
+            ("sans-serif", plot_options.px(50)).into_font(),
+        )
+        .margin(plot_options.px(10))
+        .margin_bottom(plot_options.px(70))
+        .x_label_area_size(plot_options.px(30))
+        .y_label_area_size(plot_options.px(30))
         .build_cartesian_2d((0..num_log_levels - 1).into_segmented(), 0..max_count)
         .unwrap();
 
     _ = chart
         .configure_mesh()
         .disable_x_mesh()
-        .x_label_offset(5)
+        .x_label_offset(plot_options.px(5))
         .x_labels(num_log_levels)
-        .set_tick_mark_size(LabelAreaPosition::Bottom, 20)
+        .set_tick_mark_size(LabelAreaPosition::Bottom, plot_options.px(20))
         .x_label_formatter(&|x| match x {
-            SegmentValue::CenterOf(v) => loglevels[*v as usize].to_owned(),
+            SegmentValue::CenterOf(v) => loglevels[*v].to_owned(),
             _ => "UNK".to_string(),
         })
-        .x_label_style(TextStyle::from(("sans-serif", 24).into_font()))
+        .x_label_style(TextStyle::from(
+            ("sans-serif", plot_options.px(24)).into_font(),
+        ))
+        .y_label_style(("sans-serif", plot_options.px(12)))
         .draw();
 
     chart
@@ -128,18 +273,669 @@ pub fn plot_combined_bar_chart(
     _ = root.present();
 }
 
-fn get_tick_size(dt_cat: &DateTimeCat) -> i32 {
+pub fn plot_stacked_timeline(
+    plot_gen_dir: &str,
+    logs_aggregate: &LogsAggregate,
+    gaps: &[Gap],
+    y_scale: YScale,
+    plot_options: &PlotOptions,
+) {
+    let plot_file_path = plot_options.plot_file_path(plot_gen_dir, "timeline");
+    let size = plot_options.size();
+
+    match plot_options.format {
+        PlotFormat::Png => draw_stacked_timeline(
+            BitMapBackend::new(&plot_file_path, size).into_drawing_area(),
+            logs_aggregate,
+            gaps,
+            y_scale,
+            plot_options,
+        ),
+        PlotFormat::Svg => draw_stacked_timeline(
+            SVGBackend::new(&plot_file_path, size).into_drawing_area(),
+            logs_aggregate,
+            gaps,
+            y_scale,
+            plot_options,
+        ),
+    }
+}
+
+fn draw_stacked_timeline<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    logs_aggregate: &LogsAggregate,
+    gaps: &[Gap],
+    y_scale: YScale,
+    plot_options: &PlotOptions,
+) {
+    let num_dt = logs_aggregate.datetimes.len();
+    let stacks = stack_log_counts(logs_aggregate, y_scale);
+    let max_total = stacks
+        .iter()
+        .filter_map(|levels| levels.last().map(|(_, _, upper)| *upper))
+        .fold(0.0, f64::max);
+
+    _ = root.fill(&WHITE);
+    let mut chart_builder = ChartBuilder::on(&root);
+    chart_builder
+        .caption(
+            "Log Timeline",
+            ("sans-serif", plot_options.px(50)).into_font(),
+        )
+        .margin(plot_options.px(10))
+        .margin_bottom(plot_options.px(20))
+        .x_label_area_size(plot_options.px(TIME_AXIS_LABEL_AREA))
+        .y_label_area_size(plot_options.px(50));
+
+    match y_scale {
+        YScale::Linear => {
+            let chart = chart_builder
+                .build_cartesian_2d(0..num_dt.max(1), 0.0..f64::max(max_total, 1.0))
+                .unwrap();
+            draw_stacks(
+                &root,
+                chart,
+                logs_aggregate,
+                gaps,
+                &stacks,
+                "",
+                plot_options,
+            );
+        }
+        YScale::Log => {
+            let chart = chart_builder
+                .build_cartesian_2d(
+                    0..num_dt.max(1),
+                    (1.0..f64::max(max_total, 10.0)).log_scale(),
+                )
+                .unwrap();
+            draw_stacks(
+                &root,
+                chart,
+                logs_aggregate,
+                gaps,
+                &stacks,
+                "",
+                plot_options,
+            );
+        }
+        YScale::Percent => {
+            let chart = chart_builder
+                .build_cartesian_2d(0..num_dt.max(1), 0.0..100.0)
+                .unwrap();
+            draw_stacks(
+                &root,
+                chart,
+                logs_aggregate,
+                gaps,
+                &stacks,
+                "%",
+                plot_options,
+            );
+        }
+    }
+
+    _ = root.present();
+}
+
+// For every bucket gives the (loglevel, lower, upper) bounds of each level
+// stacked on top of the more severe ones, in the unit of the y scale.
+fn stack_log_counts(
+    logs_aggregate: &LogsAggregate,
+    y_scale: YScale,
+) -> Vec<Vec<(LogLevel, f64, f64)>> {
+    return (0..logs_aggregate.datetimes.len())
+        .map(|idx| {
+            let total: i32 = LOGLEVELS
+                .iter()
+                .map(|loglevel| logs_aggregate.aggregates[loglevel][idx])
+                .sum();
+            let mut lower = 0.0;
+            LOGLEVELS
+                .iter()
+                .map(|loglevel| {
+                    let count = logs_aggregate.aggregates[loglevel][idx] as f64;
+                    let height = match y_scale {
+                        YScale::Percent if total > 0 => count * 100.0 / total as f64,
+                        YScale::Percent => 0.0,
+                        _ => count,
+                    };
+                    let bounds = (*loglevel, lower, lower + height);
+                    lower += height;
+                    bounds
+                })
+                .collect()
+        })
+        .collect();
+}
+
+fn draw_stacks<'a, DB, Y>(
+    root: &DrawingArea<DB, Shift>,
+    mut chart: ChartContext<'a, DB, Cartesian2d<RangedCoordusize, Y>>,
+    logs_aggregate: &LogsAggregate,
+    gaps: &[Gap],
+    stacks: &[Vec<(LogLevel, f64, f64)>],
+    y_label_suffix: &str,
+    plot_options: &PlotOptions,
+) where
+    DB: DrawingBackend + 'a,
+    Y: Ranged<ValueType = f64> + ValueFormatter<f64>,
+{
+    // Log scale can not show zero, so the lowest stack starts at one there
+    let y_floor = chart.y_range().start.max(0.0);
+
+    _ = chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(1)
+        .x_label_formatter(&|_| String::new())
+        .set_tick_mark_size(LabelAreaPosition::Bottom, 0)
+        .y_label_formatter(&|y| format!("{}{}", format_y_label(*y), y_label_suffix))
+        .y_label_style(("sans-serif", plot_options.px(12)))
+        .draw();
+    draw_time_axis(root, &chart, logs_aggregate, plot_options);
+
+    // Shade every bucket touched by a gap, behind the stacks
+    let y_range = chart.y_range();
+    _ = chart.draw_series(gaps.iter().map(|gap| {
+        let first = logs_aggregate
+            .datetimes
+            .partition_point(|bucket| *bucket <= gap.start)
+            .saturating_sub(1);
+        let last = logs_aggregate
+            .datetimes
+            .partition_point(|bucket| *bucket < gap.end);
+        Rectangle::new(
+            [(first, y_range.start), (last.max(first + 1), y_range.end)],
+            GAP_GREY.mix(0.3).filled(),
+        )
+    }));
+
+    for loglevel in LOGLEVELS.iter() {
+        if logs_aggregate.aggregates[loglevel]
+            .iter()
+            .all(|count| *count == 0)
+        {
+            continue;
+        }
+
+        let color = get_color(loglevel);
+        let rects = stacks.iter().enumerate().filter_map(|(idx, levels)| {
+            let (_, lower, upper) = levels.iter().find(|(level, _, _)| level == loglevel)?;
+            if upper <= lower {
+                return None;
+            }
+            Some(Rectangle::new(
+                [(idx, lower.max(y_floor)), (idx + 1, upper.max(y_floor))],
+                color.filled(),
+            ))
+        });
+
+        let legend_size = plot_options.px(10);
+        chart
+            .draw_series(rects)
+            .unwrap()
+            .label(format!("{:?}", loglevel))
+            .legend(move |(x, y)| {
+                Rectangle::new(
+                    [
+                        (x, y - legend_size / 2),
+                        (x + legend_size, y + legend_size / 2),
+                    ],
+                    color.filled(),
+                )
+            });
+    }
+
+    _ = chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .label_font(("sans-serif", plot_options.px(14)))
+        .draw();
+}
+
+pub fn plot_cumulative_counts(
+    plot_gen_dir: &str,
+    logs_aggregate: &LogsAggregate,
+    metrics: &LogMetrics,
+    plot_options: &PlotOptions,
+) {
+    let plot_file_path = plot_options.plot_file_path(plot_gen_dir, "cumulative");
+    let size = plot_options.size();
+
+    match plot_options.format {
+        PlotFormat::Png => draw_cumulative_counts(
+            BitMapBackend::new(&plot_file_path, size).into_drawing_area(),
+            logs_aggregate,
+            metrics,
+            plot_options,
+        ),
+        PlotFormat::Svg => draw_cumulative_counts(
+            SVGBackend::new(&plot_file_path, size).into_drawing_area(),
+            logs_aggregate,
+            metrics,
+            plot_options,
+        ),
+    }
+}
+
+fn draw_cumulative_counts<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    logs_aggregate: &LogsAggregate,
+    metrics: &LogMetrics,
+    plot_options: &PlotOptions,
+) {
+    let num_dt = logs_aggregate.datetimes.len();
+    let max_count = metrics
+        .cumulative_counts
+        .values()
+        .filter_map(|counts| counts.last().copied())
+        .max()
+        .unwrap_or(0);
+
+    _ = root.fill(&WHITE);
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            "Cumulative Loglevel Count",
+            ("sans-serif", plot_options.px(50)).into_font(),
+        )
+        .margin(plot_options.px(10))
+        .margin_right(plot_options.px(20))
+        .margin_bottom(plot_options.px(20))
+        .x_label_area_size(plot_options.px(TIME_AXIS_LABEL_AREA))
+        .y_label_area_size(plot_options.px(50))
+        .build_cartesian_2d(0..num_dt.max(1), 0..max_count.max(1))
+        .unwrap();
+
+    _ = chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(1)
+        .x_label_formatter(&|_| String::new())
+        .set_tick_mark_size(LabelAreaPosition::Bottom, 0)
+        .y_label_style(("sans-serif", plot_options.px(12)))
+        .draw();
+    draw_time_axis(&root, &chart, logs_aggregate, plot_options);
+
+    for loglevel in LOGLEVELS.iter() {
+        let counts = &metrics.cumulative_counts[loglevel];
+        if counts.last().copied().unwrap_or(0) == 0 {
+            continue;
+        }
+
+        // Each point sits at the end of its bucket, when all its logs are counted
+        let color = get_color(loglevel);
+        let points = std::iter::once((0, 0)).chain(
+            counts
+                .iter()
+                .enumerate()
+                .map(|(idx, count)| (idx + 1, *count)),
+        );
+        let legend_size = plot_options.px(10);
+        chart
+            .draw_series(LineSeries::new(
+                points,
+                color.stroke_width(plot_options.px(2) as u32),
+            ))
+            .unwrap()
+            .label(format!("{:?}", loglevel))
+            .legend(move |(x, y)| {
+                Rectangle::new(
+                    [
+                        (x, y - legend_size / 2),
+                        (x + legend_size, y + legend_size / 2),
+                    ],
+                    color.filled(),
+                )
+            });
+    }
+
+    _ = chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .label_font(("sans-serif", plot_options.px(14)))
+        .draw();
+
+    _ = root.present();
+}
+
+pub fn plot_hour_weekday_heatmaps(
+    plot_gen_dir: &str,
+    hour_weekday_counts: &HashMap<LogLevel, HourWeekdayCounts>,
+    plot_options: &PlotOptions,
+) {
+    let heatmaps = [
+        (
+            "heatmap_hour_weekday",
+            "All Logs by Hour and Weekday",
+            &LOGLEVELS[..],
+        ),
+        (
+            "heatmap_hour_weekday_errors",
+            "Errors and above by Hour and Weekday",
+            &LOGLEVELS[..4],
+        ),
+    ];
+
+    for (name, caption, loglevels) in heatmaps {
+        let mut counts: HourWeekdayCounts = [[0; 24]; 7];
+        for loglevel in loglevels {
+            for (weekday, hours) in hour_weekday_counts[loglevel].iter().enumerate() {
+                for (hour, count) in hours.iter().enumerate() {
+                    counts[weekday][hour] += count;
+                }
+            }
+        }
+
+        let plot_file_path = plot_options.plot_file_path(plot_gen_dir, name);
+        let size = plot_options.size();
+        match plot_options.format {
+            PlotFormat::Png => draw_hour_weekday_heatmap(
+                BitMapBackend::new(&plot_file_path, size).into_drawing_area(),
+                caption,
+                &counts,
+                plot_options,
+            ),
+            PlotFormat::Svg => draw_hour_weekday_heatmap(
+                SVGBackend::new(&plot_file_path, size).into_drawing_area(),
+                caption,
+                &counts,
+                plot_options,
+            ),
+        }
+    }
+}
+
+fn draw_hour_weekday_heatmap<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    caption: &str,
+    counts: &HourWeekdayCounts,
+    plot_options: &PlotOptions,
+) {
+    let max_count = counts.iter().flatten().max().copied().unwrap_or(0).max(1);
+
+    _ = root.fill(&WHITE);
+    let mut chart = ChartBuilder::on(&root)
+        .caption(caption, ("sans-serif", plot_options.px(40)).into_font())
+        .margin(plot_options.px(10))
+        .margin_right(plot_options.px(20))
+        .x_label_area_size(plot_options.px(30))
+        .y_label_area_size(plot_options.px(50))
+        .build_cartesian_2d((0usize..23).into_segmented(), (0usize..6).into_segmented())
+        .unwrap();
+
+    _ = chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_labels(24)
+        .y_labels(7)
+        .x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(hour) => format!("{:02}:00", hour),
+            _ => "".to_string(),
+        })
+        .y_label_formatter(&|y| match y {
+            SegmentValue::CenterOf(row) => WEEKDAYS[6 - *row].to_string(),
+            _ => "".to_string(),
+        })
+        .label_style(("sans-serif", plot_options.px(12)))
+        .draw();
+
+    let cell_width = chart.plotting_area().dim_in_pixel().0 / 24;
+    let count_style = TextStyle::from(("sans-serif", plot_options.px(11)).into_font())
+        .pos(Pos::new(HPos::Center, VPos::Center));
+
+    for (weekday, hours) in counts.iter().enumerate() {
+        // Monday goes on the top row
+        let row = 6 - weekday;
+        for (hour, count) in hours.iter().enumerate() {
+            let ratio = *count as f64 / max_count as f64;
+            _ = chart.draw_series([Rectangle::new(
+                [
+                    (SegmentValue::Exact(hour), SegmentValue::Exact(row)),
+                    (SegmentValue::Exact(hour + 1), SegmentValue::Exact(row + 1)),
+                ],
+                get_heat_color(ratio, &HEAT_BLUE).filled(),
+            )]);
+
+            // Only write the numbers when they fit into the cells
+            if *count > 0 && cell_width as i32 >= plot_options.px(40) {
+                let text_color = if ratio > 0.6 { WHITE } else { BLACK };
+                _ = chart.draw_series([Text::new(
+                    format!("{}", count),
+                    (SegmentValue::CenterOf(hour), SegmentValue::CenterOf(row)),
+                    count_style.color(&text_color),
+                )]);
+            }
+        }
+    }
+
+    _ = root.present();
+}
+
+pub fn plot_level_time_heatmap(
+    plot_gen_dir: &str,
+    logs_aggregate: &LogsAggregate,
+    plot_options: &PlotOptions,
+) {
+    let plot_file_path = plot_options.plot_file_path(plot_gen_dir, "heatmap_level_time");
+    let size = plot_options.size();
+
+    match plot_options.format {
+        PlotFormat::Png => draw_level_time_heatmap(
+            BitMapBackend::new(&plot_file_path, size).into_drawing_area(),
+            logs_aggregate,
+            plot_options,
+        ),
+        PlotFormat::Svg => draw_level_time_heatmap(
+            SVGBackend::new(&plot_file_path, size).into_drawing_area(),
+            logs_aggregate,
+            plot_options,
+        ),
+    }
+}
+
+// Each row is coloured relative to the busiest bucket of its own level,
+// otherwise the info and debug rows would wash out every other level.
+fn draw_level_time_heatmap<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    logs_aggregate: &LogsAggregate,
+    plot_options: &PlotOptions,
+) {
+    let num_dt = logs_aggregate.datetimes.len();
+    let num_log_levels = LOGLEVELS.len();
+
+    _ = root.fill(&WHITE);
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            "Log Levels over Time",
+            ("sans-serif", plot_options.px(40)).into_font(),
+        )
+        .margin(plot_options.px(10))
+        .margin_right(plot_options.px(20))
+        .margin_bottom(plot_options.px(20))
+        .x_label_area_size(plot_options.px(TIME_AXIS_LABEL_AREA))
+        .y_label_area_size(plot_options.px(80))
+        .build_cartesian_2d(0..num_dt.max(1), (0..num_log_levels - 1).into_segmented())
+        .unwrap();
+
+    _ = chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_labels(1)
+        .x_label_formatter(&|_| String::new())
+        .set_tick_mark_size(LabelAreaPosition::Bottom, 0)
+        .y_labels(num_log_levels)
+        .y_label_formatter(&|y| match y {
+            SegmentValue::CenterOf(row) => format!("{:?}", LOGLEVELS[*row]),
+            _ => "".to_string(),
+        })
+        .y_label_style(("sans-serif", plot_options.px(14)))
+        .draw();
+    draw_time_axis(&root, &chart, logs_aggregate, plot_options);
+
+    for (row, loglevel) in LOGLEVELS.iter().enumerate() {
+        let counts = &logs_aggregate.aggregates[loglevel];
+        let max_count = counts.iter().max().copied().unwrap_or(0).max(1);
+        let base_color = get_color(loglevel);
+        _ = chart.draw_series(counts.iter().enumerate().map(|(idx, count)| {
+            Rectangle::new(
+                [
+                    (idx, SegmentValue::Exact(row)),
+                    (idx + 1, SegmentValue::Exact(row + 1)),
+                ],
+                get_heat_color(*count as f64 / max_count as f64, &base_color).filled(),
+            )
+        }));
+    }
+
+    _ = root.present();
+}
+
+// Blends from white for no logs to the given color for the highest count
+fn get_heat_color(ratio: f64, color: &RGBColor) -> RGBColor {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let blend = |channel: u8| (255.0 - (255.0 - channel as f64) * ratio).round() as u8;
+    return RGBColor(blend(color.0), blend(color.1), blend(color.2));
+}
+
+// Rounds away float noise like 0.30000000000000004 from the mesh values
+fn format_y_label(value: f64) -> String {
+    let label = format!("{:.2}", value);
+    return label.trim_end_matches('0').trim_end_matches('.').to_owned();
+}
+
+// Draws the labels of the time axis below the chart. Labels are thinned out
+// to fit the width of the chart and a second row marks where a larger unit,
+// like a new day below the hour labels, begins.
+fn draw_time_axis<DB, Y>(
+    root: &DrawingArea<DB, Shift>,
+    chart: &ChartContext<DB, Cartesian2d<RangedCoordusize, Y>>,
+    logs_aggregate: &LogsAggregate,
+    plot_options: &PlotOptions,
+) where
+    DB: DrawingBackend,
+    Y: Ranged,
+    Y::ValueType: Clone,
+{
+    let datetimes = &logs_aggregate.datetimes;
+    if datetimes.is_empty() {
+        return;
+    }
+
+    let (label_fmt, marker_fmt) = get_axis_dt_fmts(&logs_aggregate.datetime_cat);
+    let label_style = TextStyle::from(("sans-serif", plot_options.px(12)).into_font())
+        .pos(Pos::new(HPos::Center, VPos::Top));
+    let marker_style = TextStyle::from(
+        ("sans-serif", plot_options.px(13))
+            .into_font()
+            .style(FontStyle::Bold),
+    )
+    .pos(Pos::new(HPos::Left, VPos::Top));
+    let separator_style = GREY.mix(0.6).stroke_width(1);
+
+    let sample_label = datetimes[0].format(label_fmt).to_string();
+    let label_width = match root.estimate_text_size(&sample_label, &label_style) {
+        Ok((width, _)) => width as i32,
+        Err(_) => sample_label.len() as i32 * plot_options.px(8),
+    } + plot_options.px(12);
+    let plot_width = chart.plotting_area().dim_in_pixel().0 as i32;
+    let max_labels = (plot_width / label_width.max(1)).max(1) as usize;
+    let step = get_label_step(&logs_aggregate.datetime_cat, datetimes.len(), max_labels);
+
+    let y_bottom = chart.y_range().start;
+    let y_top = chart.y_range().end;
+    let mut last_marker_end = i32::MIN;
+    for (idx, datetime) in datetimes.iter().enumerate() {
+        let (x, y) = chart.backend_coord(&(idx, y_bottom.clone()));
+
+        if get_dt_unit_index(datetime, &logs_aggregate.datetime_cat) % step as i64 == 0 {
+            _ = root.draw(&PathElement::new(
+                vec![(x, y), (x, y + plot_options.px(5))],
+                BLACK,
+            ));
+            _ = root.draw(&Text::new(
+                datetime.format(label_fmt).to_string(),
+                (x, y + plot_options.px(8)),
+                label_style.clone(),
+            ));
+        }
+
+        let Some(marker_fmt) = marker_fmt else {
+            continue;
+        };
+        let marker = datetime.format(marker_fmt).to_string();
+        if idx > 0 && datetimes[idx - 1].format(marker_fmt).to_string() == marker {
+            continue;
+        }
+        if idx > 0 {
+            let (_, y_top_px) = chart.backend_coord(&(idx, y_top.clone()));
+            _ = root.draw(&PathElement::new(
+                vec![(x, y_top_px), (x, y + plot_options.px(40))],
+                separator_style,
+            ));
+        }
+        if x > last_marker_end {
+            let marker_width = match root.estimate_text_size(&marker, &marker_style) {
+                Ok((width, _)) => width as i32,
+                Err(_) => marker.len() as i32 * plot_options.px(9),
+            };
+            _ = root.draw(&Text::new(
+                marker,
+                (x + plot_options.px(3), y + plot_options.px(28)),
+                marker_style.clone(),
+            ));
+            last_marker_end = x + marker_width + plot_options.px(10);
+        }
+    }
+}
+
+// Picks the smallest step between two labels that lines up with the clock
+// or calendar, eg: every 15 minutes, and still fits into max_labels.
+fn get_label_step(dt_cat: &DateTimeCat, num_dt: usize, max_labels: usize) -> usize {
+    let steps: &[usize] = match dt_cat {
+        DateTimeCat::Seconds => &[1, 2, 5, 10, 15, 30, 60],
+        DateTimeCat::Minutes => &[1, 2, 5, 10, 15, 30, 60],
+        DateTimeCat::Hours => &[1, 2, 3, 6, 12, 24],
+        DateTimeCat::Days => &[1, 2, 7, 14],
+        DateTimeCat::Months => &[1, 2, 3, 6, 12],
+        DateTimeCat::Years => &[1, 2, 5, 10, 20, 50, 100],
+    };
+
+    let mut step = steps[0];
+    for &candidate in steps {
+        step = candidate;
+        if num_dt.div_ceil(step) <= max_labels {
+            return step;
+        }
+    }
+    while num_dt.div_ceil(step) > max_labels {
+        step *= 2;
+    }
+
+    return step;
+}
+
+fn get_dt_unit_index(datetime: &NaiveDateTime, dt_cat: &DateTimeCat) -> i64 {
+    let timestamp = datetime.and_utc().timestamp();
     return match dt_cat {
-        DateTimeCat::Seconds => 40,
-        DateTimeCat::Minutes => 35,
-        DateTimeCat::Hours => 35,
-        DateTimeCat::Days => 28,
-        DateTimeCat::Months => 25,
-        DateTimeCat::Years => 15,
+        DateTimeCat::Seconds => timestamp,
+        DateTimeCat::Minutes => timestamp.div_euclid(60),
+        DateTimeCat::Hours => timestamp.div_euclid(60 * 60),
+        DateTimeCat::Days => datetime.num_days_from_ce() as i64,
+        DateTimeCat::Months => datetime.year() as i64 * 12 + datetime.month0() as i64,
+        DateTimeCat::Years => datetime.year() as i64,
     };
 }
 
 fn get_color_style(loglevel: &LogLevel) -> ShapeStyle {
+    return get_color(loglevel).filled();
+}
+
+pub fn get_color(loglevel: &LogLevel) -> RGBColor {
     let loglevel_color_map: HashMap<&LogLevel, RGBColor> = HashMap::from([
         (&LogLevel::Emergency, RED_700),
         (&LogLevel::Alert, RED_600),
@@ -152,5 +948,5 @@ fn get_color_style(loglevel: &LogLevel) -> ShapeStyle {
         (&LogLevel::Others, GREY),
     ]);
 
-    return loglevel_color_map[loglevel].filled();
+    return loglevel_color_map[loglevel];
 }
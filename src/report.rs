@@ -1,161 +1,440 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::{collections::HashMap, fs::File, io::Write};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{read, read_to_string, File},
+    io::Write,
+    path::Path,
+};
 
-use crate::analyse::loglevel::LogLevel;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use minijinja::{AutoEscape, Environment};
+use regex::{Captures, Regex};
 
-pub fn generate_html_report(
-    report_dir: &str,
-    no_of_files: i32,
-    no_of_logs: i32,
-    combined_loglevel_count: &HashMap<LogLevel, i32>,
-    user: Option<String>,
-) {
-    let mut html_text: String = include_str!("../assets/index.html").to_owned();
-    let html_report_path = format!("{}", report_dir) + "/report.html";
+use serde::Serialize;
+
+use crate::analyse::{
+    dtfmt::{format_duration, get_dt_fmt, GENERATED_AT_FMT, TIME_SPAN_FMT},
+    gaps::GapKind,
+    loglevel::{LogLevel, LOGLEVELS},
+    metrics::BucketCount,
+    LogsAggregate,
+};
+use crate::analysis::Analysis;
+use crate::fileops::{prepare_plots_gen_dir, remove_plots_gen_dir};
+use crate::output::OutputRenderer;
+use crate::plot::{
+    get_color, plot_combined_bar_chart, plot_cumulative_counts, plot_histograms,
+    plot_hour_weekday_heatmaps, plot_level_time_heatmap, plot_stacked_timeline, PlotOptions,
+    YScale,
+};
+
+/// Template of the html report when no other is given
+pub const DEFAULT_TEMPLATE: &str = include_str!("../assets/index.html");
+
+pub struct HtmlReportOptions {
+    /// Name shown in the report as the one who generated it
+    pub user: Option<String>,
+    /// Embed the plots into report.html instead of keeping them in `plots/`
+    pub self_contained: bool,
+    /// Draw the log trends as one interactive chart instead of a plot per loglevel
+    pub interactive: bool,
+    pub y_scale: YScale,
+    pub plot_options: PlotOptions,
+    /// Jinja template the report is rendered with instead of [`DEFAULT_TEMPLATE`]
+    pub template: Option<String>,
+}
+
+/// Values given to the report template. Fields are only ever added to it, so
+/// that custom templates keep working with newer versions.
+#[derive(Serialize)]
+pub struct ReportContext {
+    pub version: &'static str,
+    pub user: Option<String>,
+    pub files: i32,
+    pub logs: i32,
+    /// Extension of the plots in `plots/`, eg: "png"
+    pub plot_extension: &'static str,
+    pub interactive: bool,
+    /// Every loglevel from the most to the least severe, with or without logs
+    pub levels: Vec<LevelContext>,
+    pub metrics: MetricsContext,
+    /// Firings of the alert rules, none when no rules were given
+    pub rule_firings: Option<Vec<RuleFiringContext>>,
+    pub gaps: GapsContext,
+    pub message_patterns: Vec<MessagePatternContext>,
+    /// Html of the interactive chart, empty unless `interactive` is set
+    pub trend_chart: String,
+    pub metadata: MetadataContext,
+}
+
+#[derive(Serialize)]
+pub struct LevelContext {
+    /// Name of the loglevel, eg: "Error"
+    pub name: String,
+    /// Lowercase name used in the plot file names, eg: "error"
+    pub id: String,
+    pub count: i32,
+    /// Percentage of all the logs
+    pub share: f64,
+}
+
+#[derive(Serialize)]
+pub struct MetricsContext {
+    pub error_ratio: f64,
+    pub errors_per_minute: f64,
+    pub errors_per_hour: f64,
+    pub peak_errors_per_minute: f64,
+    pub peak_errors_per_hour: f64,
+    /// "p50", "p90", "p95" and "p99" of the logs per time bucket
+    pub volume_percentiles: BTreeMap<String, i32>,
+    pub busiest_bucket: Option<BucketContext>,
+    pub quietest_bucket: Option<BucketContext>,
+}
+
+#[derive(Serialize)]
+pub struct BucketContext {
+    pub datetime: String,
+    pub count: i32,
+}
+
+#[derive(Serialize)]
+pub struct RuleFiringContext {
+    pub rule: String,
+    pub severity: String,
+    pub condition: String,
+    pub start: String,
+    pub end: String,
+    pub value: String,
+}
+
+#[derive(Serialize)]
+pub struct GapsContext {
+    /// Shortest gap reported, eg: "20m"
+    pub threshold: String,
+    pub silence_ratio: f64,
+    pub gaps: Vec<GapContext>,
+}
+
+#[derive(Serialize)]
+pub struct GapContext {
+    pub source: String,
+    pub kind: &'static str,
+    pub start: String,
+    pub end: String,
+    pub duration: String,
+}
+
+#[derive(Serialize)]
+pub struct MessagePatternContext {
+    pub level: String,
+    pub pattern: String,
+    pub count: i32,
+    pub example: String,
+}
+
+#[derive(Serialize)]
+pub struct MetadataContext {
+    pub generated_at: String,
+    pub inputs: Vec<String>,
+    /// Name of the parser, "custom" for parsers given by library users
+    pub parser: String,
+    pub datetime_format: String,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub first_log: Option<String>,
+    pub last_log: Option<String>,
+    pub granularity: String,
+    pub lines_read: i32,
+    pub lines_unparsed: i32,
+    /// Percentage of the lines read that were parsed
+    pub coverage: f64,
+}
+
+#[derive(Serialize)]
+struct ChartData {
+    labels: Vec<String>,
+    series: Vec<ChartSeries>,
+}
+
+#[derive(Serialize)]
+struct ChartSeries {
+    name: String,
+    color: String,
+    values: Vec<i32>,
+}
+
+/// Writes `report.html` along with its plots.
+pub struct HtmlRenderer {
+    pub options: HtmlReportOptions,
+}
+
+impl OutputRenderer for HtmlRenderer {
+    fn name(&self) -> &'static str {
+        return "html";
+    }
+
+    fn render(&self, report_dir: &str, analysis: &Analysis) {
+        generate_html_report(report_dir, analysis, &self.options);
+    }
+
+    // The default template does not show them, a custom one may
+    fn shows_message_patterns(&self) -> bool {
+        return self.options.template.is_some();
+    }
+}
 
-    let value_map = prepare_placeholder_map(no_of_files, no_of_logs, combined_loglevel_count, user);
+/// Draws the plots into `report_dir/plots` and writes `report_dir/report.html`.
+pub fn generate_html_report(report_dir: &str, analysis: &Analysis, options: &HtmlReportOptions) {
+    let logs_analysis = &analysis.logs_analysis;
+    let logs_aggregate = &logs_analysis.logs_aggregate;
+    let combined_loglevel_count = &analysis.combined_loglevel_count;
+    let gaps = &logs_analysis.gaps;
+    let plot_options = &options.plot_options;
+    let html_report_path = report_dir.to_string() + "/report.html";
 
-    html_text = replace_placeholders(html_text, &value_map);
+    println!("Preparing the plots...");
+    let plots_gen_dir = prepare_plots_gen_dir(report_dir);
+    plot_stacked_timeline(
+        &plots_gen_dir,
+        logs_aggregate,
+        &gaps.gaps,
+        options.y_scale,
+        plot_options,
+    );
+    plot_cumulative_counts(
+        &plots_gen_dir,
+        logs_aggregate,
+        &logs_analysis.metrics,
+        plot_options,
+    );
+    plot_hour_weekday_heatmaps(
+        &plots_gen_dir,
+        &logs_analysis.hour_weekday_counts,
+        plot_options,
+    );
+    plot_level_time_heatmap(&plots_gen_dir, logs_aggregate, plot_options);
+    if !options.interactive {
+        plot_histograms(&plots_gen_dir, logs_aggregate, plot_options);
+    }
+    plot_combined_bar_chart(&plots_gen_dir, combined_loglevel_count, plot_options);
+
+    let context = ReportContext::new(analysis, options);
+    let template = options.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    let mut html_text = render_template(template, &context)
+        .unwrap_or_else(|err| panic!("Error while rendering the report template: {}", err));
+    if options.self_contained {
+        html_text = inline_plots(html_text, report_dir);
+    }
     let mut file = File::create(html_report_path).unwrap();
     file.write_all(html_text.as_bytes()).unwrap();
+
+    if options.self_contained {
+        remove_plots_gen_dir(&plots_gen_dir);
+    }
 }
 
-fn prepare_placeholder_map(
-    no_of_files: i32,
-    no_of_logs: i32,
-    combined_loglevel_count: &HashMap<LogLevel, i32>,
-    user: Option<String>,
-) -> HashMap<&str, String> {
-    return HashMap::from([
-        ("NO_OF_FILES", format!("{}", no_of_files)),
-        ("NO_OF_LOGS", format!("{}", no_of_logs)),
-        (
-            "NO_OF_ALERT",
-            format!("{}", combined_loglevel_count[&LogLevel::Alert]),
-        ),
-        (
-            "NO_OF_CRITICAL",
-            format!("{}", combined_loglevel_count[&LogLevel::Critical]),
-        ),
-        (
-            "NO_OF_EMERGENCY",
-            format!("{}", combined_loglevel_count[&LogLevel::Emergency]),
-        ),
-        (
-            "NO_OF_ERROR",
-            format!("{}", combined_loglevel_count[&LogLevel::Error]),
-        ),
-        (
-            "NO_OF_WARNING",
-            format!("{}", combined_loglevel_count[&LogLevel::Warning]),
-        ),
-        (
-            "NO_OF_NOTICE",
-            format!("{}", combined_loglevel_count[&LogLevel::Notice]),
-        ),
-        (
-            "NO_OF_INFO",
-            format!("{}", combined_loglevel_count[&LogLevel::Info]),
-        ),
-        (
-            "NO_OF_DEBUG",
-            format!("{}", combined_loglevel_count[&LogLevel::Debug]),
-        ),
-        (
-            "NO_OF_OTHERS",
-            format!("{}", combined_loglevel_count[&LogLevel::Others]),
-        ),
-        (
-            "ALERT_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Alert] <= 0 {
-                "hide".to_owned()
-            } else {
-                "".to_owned()
-            },
-        ),
-        (
-            "CRITICAL_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Critical] <= 0 {
-                "hide".to_owned()
-            } else {
-                "".to_owned()
-            },
-        ),
-        (
-            "EMERGENCY_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Emergency] <= 0 {
-                "hide".to_owned()
-            } else {
-                "".to_owned()
-            },
-        ),
-        (
-            "ERROR_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Error] <= 0 {
-                "hide".to_owned()
-            } else {
-                "".to_owned()
-            },
-        ),
-        (
-            "WARNING_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Warning] <= 0 {
-                "hide".to_owned()
-            } else {
-                "".to_owned()
-            },
-        ),
-        (
-            "NOTICE_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Notice] <= 0 {
-                "hide".to_owned()
-            } else {
-                "".to_owned()
-            },
-        ),
-        (
-            "INFO_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Info] <= 0 {
-                "hide".to_owned()
-            } else {
-                "".to_owned()
+impl ReportContext {
+    pub fn new(analysis: &Analysis, options: &HtmlReportOptions) -> ReportContext {
+        let logs_analysis = &analysis.logs_analysis;
+        let logs_aggregate = &logs_analysis.logs_aggregate;
+        let combined_loglevel_count = &analysis.combined_loglevel_count;
+        let metrics = &logs_analysis.metrics;
+        let metadata = &analysis.metadata;
+        let dt_fmt = get_dt_fmt(&logs_aggregate.datetime_cat);
+        let total: i32 = combined_loglevel_count.values().sum();
+
+        let bucket_context = |bucket: &Option<BucketCount>| {
+            bucket.as_ref().map(|bucket| BucketContext {
+                datetime: bucket.datetime.format(&dt_fmt).to_string(),
+                count: bucket.count,
+            })
+        };
+
+        return ReportContext {
+            version: env!("CARGO_PKG_VERSION"),
+            user: options.user.clone().filter(|user| !user.is_empty()),
+            files: analysis.no_of_files,
+            logs: analysis.no_of_logs,
+            plot_extension: options.plot_options.extension(),
+            interactive: options.interactive,
+            levels: LOGLEVELS
+                .iter()
+                .map(|loglevel| {
+                    let count = combined_loglevel_count[loglevel];
+                    LevelContext {
+                        name: format!("{:?}", loglevel),
+                        id: format!("{:?}", loglevel).to_lowercase(),
+                        count,
+                        share: if total > 0 {
+                            count as f64 * 100.0 / total as f64
+                        } else {
+                            0.0
+                        },
+                    }
+                })
+                .collect(),
+            metrics: MetricsContext {
+                error_ratio: metrics.error_ratio,
+                errors_per_minute: metrics.errors_per_minute,
+                errors_per_hour: metrics.errors_per_hour,
+                peak_errors_per_minute: metrics.peak_errors_per_minute,
+                peak_errors_per_hour: metrics.peak_errors_per_hour,
+                volume_percentiles: metrics.volume_percentiles.clone(),
+                busiest_bucket: bucket_context(&metrics.busiest_bucket),
+                quietest_bucket: bucket_context(&metrics.quietest_bucket),
             },
-        ),
-        (
-            "DEBUG_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Debug] <= 0 {
-                "hide".to_owned()
-            } else {
-                "".to_owned()
+            rule_firings: logs_analysis.rule_firings.as_ref().map(|rule_firings| {
+                rule_firings
+                    .iter()
+                    .map(|firing| RuleFiringContext {
+                        rule: firing.rule.clone(),
+                        severity: format!("{:?}", firing.severity),
+                        condition: firing.condition.clone(),
+                        start: firing.start.format(&dt_fmt).to_string(),
+                        end: firing.end.format(&dt_fmt).to_string(),
+                        value: firing.value_text(),
+                    })
+                    .collect()
+            }),
+            gaps: GapsContext {
+                threshold: format_duration(logs_analysis.gaps.min_gap_secs),
+                silence_ratio: logs_analysis.gaps.silence_ratio,
+                gaps: logs_analysis
+                    .gaps
+                    .gaps
+                    .iter()
+                    .map(|gap| GapContext {
+                        source: gap.source.clone(),
+                        kind: match gap.kind {
+                            GapKind::NoLogs => "No logs",
+                            GapKind::LowVolume => "Low volume",
+                        },
+                        start: gap.start.format(&dt_fmt).to_string(),
+                        end: gap.end.format(&dt_fmt).to_string(),
+                        duration: format_duration(gap.duration_secs),
+                    })
+                    .collect(),
             },
-        ),
-        (
-            "OTHERS_VISIBILITY",
-            if combined_loglevel_count[&LogLevel::Others] <= 0 {
-                "hide".to_owned()
+            message_patterns: logs_analysis
+                .message_patterns
+                .iter()
+                .map(|message_pattern| MessagePatternContext {
+                    level: format!("{:?}", message_pattern.loglevel),
+                    pattern: message_pattern.pattern.clone(),
+                    count: message_pattern.count,
+                    example: message_pattern.example.clone(),
+                })
+                .collect(),
+            trend_chart: if options.interactive {
+                prepare_interactive_chart(logs_aggregate, combined_loglevel_count)
             } else {
-                "".to_owned()
+                String::new()
             },
-        ),
-        (
-            "BY_USER",
-            match user {
-                None => "".to_owned(),
-                Some(user) => match user.as_str() {
-                    "" => "".to_owned(),
-                    _ => format!(" by {}", user),
-                },
+            metadata: MetadataContext {
+                generated_at: metadata.generated_at.format(GENERATED_AT_FMT).to_string(),
+                inputs: metadata.inputs.clone(),
+                parser: metadata.parser_name(),
+                datetime_format: metadata.datetime_format.clone(),
+                include: metadata.include.clone(),
+                exclude: metadata.exclude.clone(),
+                first_log: metadata
+                    .first_log
+                    .map(|first_log| first_log.format(TIME_SPAN_FMT).to_string()),
+                last_log: metadata
+                    .last_log
+                    .map(|last_log| last_log.format(TIME_SPAN_FMT).to_string()),
+                granularity: format!("{:?}", metadata.granularity),
+                lines_read: metadata.lines_read,
+                lines_unparsed: metadata.lines_unparsed,
+                coverage: metadata.coverage,
             },
-        ),
-    ]);
+        };
+    }
 }
 
-fn replace_placeholders(text: String, value_map: &HashMap<&str, String>) -> String {
-    let mut replaced_text = text;
-    for (&from, to) in value_map {
-        replaced_text = replaced_text.replace(format!("{{#{}#}}", from).as_str(), to);
-    }
+// Every template is html, whatever the name of its file
+fn template_env() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_auto_escape_callback(|_| AutoEscape::Html);
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_keep_trailing_newline(true);
+    return env;
+}
+
+/// Renders a jinja template of the html report with the given context.
+pub fn render_template(template: &str, context: &ReportContext) -> Result<String, String> {
+    return template_env()
+        .render_str(template, context)
+        .map_err(|err| err.to_string());
+}
+
+/// Reads a report template and checks its syntax, so that a broken template
+/// is reported before the logs are analysed.
+pub fn load_template(template_path: &str) -> Result<String, String> {
+    let template = read_to_string(template_path)
+        .map_err(|err| format!("Error reading template {}: {}", template_path, err))?;
+    template_env()
+        .template_from_str(&template)
+        .map_err(|err| format!("Invalid template {}: {}", template_path, err))?;
+
+    return Ok(template);
+}
+
+// Levels without any logs are left out of the chart like the hidden plots
+fn prepare_interactive_chart(
+    logs_aggregate: &LogsAggregate,
+    combined_loglevel_count: &HashMap<LogLevel, i32>,
+) -> String {
+    let dt_fmt = get_dt_fmt(&logs_aggregate.datetime_cat);
+    let chart_data = ChartData {
+        labels: logs_aggregate
+            .datetimes
+            .iter()
+            .map(|dt| dt.format(&dt_fmt).to_string())
+            .collect(),
+        series: LOGLEVELS
+            .iter()
+            .filter(|loglevel| combined_loglevel_count[loglevel] > 0)
+            .map(|loglevel| {
+                let color = get_color(loglevel);
+                ChartSeries {
+                    name: format!("{:?}", loglevel),
+                    color: format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2),
+                    values: logs_aggregate.aggregates[loglevel].to_owned(),
+                }
+            })
+            .collect(),
+    };
+    // Keep a "</script>" in the data from closing the script tag early
+    let chart_json = serde_json::to_string(&chart_data)
+        .unwrap()
+        .replace("</", "<\\/");
+
+    return format!(
+        "<div id=\"trend-chart\" class=\"interactive-chart\"></div>\n<script>const LOGGREGATE_DATA = {};</script>\n<script>{}</script>",
+        chart_json,
+        include_str!("../assets/chart.js")
+    );
+}
+
+fn inline_plots(text: String, report_dir: &str) -> String {
+    let plot_src_regex = Regex::new(r#"src="(plots/[^"]+)""#).unwrap();
 
-    return replaced_text;
+    return plot_src_regex
+        .replace_all(&text, |caps: &Captures| {
+            let plot_path = Path::new(report_dir).join(&caps[1]);
+            let mime_type = match plot_path.extension().and_then(|ext| ext.to_str()) {
+                Some("svg") => "image/svg+xml",
+                _ => "image/png",
+            };
+            let plot_data = read(&plot_path).expect("Error while reading plot to embed in report");
+            format!(
+                r#"src="data:{};base64,{}""#,
+                mime_type,
+                STANDARD.encode(plot_data)
+            )
+        })
+        .into_owned();
 }
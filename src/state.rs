@@ -0,0 +1,368 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+use std::fs::{read_to_string, rename, write};
+use std::io::ErrorKind;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analyse::{LogSource, SourceCache};
+
+/// Version of the state file format, a state of another version is not used
+const STATE_VERSION: u64 = 4;
+
+/// Where an analysis stopped reading every log file and what it found in
+/// them, saved between runs so that the next analysis of the same files only
+/// parses what was appended to them, see
+/// [`crate::AnalysisConfig::analyse_incremental`].
+#[derive(Deserialize, Serialize)]
+pub struct AnalysisState {
+    version: u64,
+    /// Options the lines were parsed with, the files are parsed again with
+    /// other ones
+    settings: String,
+    files: Vec<FileState>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FileState {
+    name: String,
+    /// Inode of the file, a file rotated in its place has another one
+    file_id: Option<u64>,
+    /// Checksum of the bytes parsed, to find files truncated and written
+    /// again past where they were parsed
+    checksum: u64,
+    cache: SourceCache,
+}
+
+impl AnalysisState {
+    /// Reads a state saved by [`AnalysisState::save`], none when there is no
+    /// state at `path` yet or it was saved by another version of loggregate.
+    pub fn load(path: &str) -> Result<Option<AnalysisState>, String> {
+        let text = match read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(format!("Error reading state file {}: {}", path, err)),
+        };
+        let state: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|err| format!("Invalid state file {}: {}", path, err))?;
+        if state["version"].as_u64() != Some(STATE_VERSION) {
+            println!(
+                "State file {} was saved by another version, parsing all the logs again",
+                path
+            );
+            return Ok(None);
+        }
+
+        return serde_json::from_value(state)
+            .map(Some)
+            .map_err(|err| format!("Invalid state file {}: {}", path, err));
+    }
+
+    /// Writes the state to `path`. The previous state is replaced at once, so
+    /// it is kept when writing fails midway.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let tmp_path = format!("{}.tmp", path);
+        let text = serde_json::to_string(self).map_err(|err| err.to_string())?;
+        return write(&tmp_path, text)
+            .and_then(|_| rename(&tmp_path, path))
+            .map_err(|err| format!("Error writing state file {}: {}", path, err));
+    }
+
+    pub(crate) fn new(
+        settings: String,
+        log_sources: &[LogSource],
+        caches: Vec<Option<SourceCache>>,
+    ) -> AnalysisState {
+        let files = log_sources
+            .iter()
+            .zip(caches)
+            .filter_map(|(source, cache)| {
+                let cache = cache?;
+                Some(FileState {
+                    name: source.name.clone(),
+                    file_id: source.file_id,
+                    checksum: checksum(&source.bytes[..cache.offset]),
+                    cache,
+                })
+            })
+            .collect();
+
+        return AnalysisState {
+            version: STATE_VERSION,
+            settings,
+            files,
+        };
+    }
+
+    /// Caches of the sources that are still the files they were when the
+    /// state was saved, none for the other sources as they are parsed from
+    /// their start.
+    pub(crate) fn resume(
+        self,
+        settings: &str,
+        log_sources: &[LogSource],
+    ) -> Vec<Option<SourceCache>> {
+        if self.settings != settings {
+            println!("The state was saved with other parsing options, parsing all the logs again");
+            return log_sources.iter().map(|_| None).collect();
+        }
+
+        // A rotated file keeps its inode under another name, eg: app.log.1,
+        // so the files are looked up by inode and by name only without one
+        let mut files: Vec<Option<FileState>> = self.files.into_iter().map(Some).collect();
+        let by_id: HashMap<u64, usize> = files
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, file)| Some((file.as_ref()?.file_id?, idx)))
+            .collect();
+        let by_name: HashMap<String, usize> = files
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, file)| Some((file.as_ref()?.name.clone(), idx)))
+            .collect();
+        return log_sources
+            .iter()
+            .map(|source| {
+                let idx = match source.file_id.and_then(|file_id| by_id.get(&file_id)) {
+                    Some(idx) => *idx,
+                    None => {
+                        let idx = *by_name.get(&source.name)?;
+                        if source.file_id.is_some() && files[idx].as_ref()?.file_id.is_some() {
+                            println!(
+                                "{} was rotated since the state was saved, parsing it again",
+                                source.name
+                            );
+                            return None;
+                        }
+                        idx
+                    }
+                };
+                let file = files[idx].take()?;
+                let bytes: &[u8] = &source.bytes;
+                if file.cache.offset > bytes.len()
+                    || checksum(&bytes[..file.cache.offset]) != file.checksum
+                {
+                    println!(
+                        "{} was truncated or changed since the state was saved, parsing it again",
+                        source.name
+                    );
+                    return None;
+                }
+                Some(file.cache)
+            })
+            .collect();
+    }
+}
+
+// Hash of `bytes` and of their length in the manner of FNV-1a, but taken a
+// word at a time rather than a byte so that hashing a file takes a fraction
+// of the time parsing it does
+fn checksum(bytes: &[u8]) -> u64 {
+    const PRIME: u64 = 0x100000001b3;
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let words = bytes.chunks_exact(8);
+    let rest = words.remainder();
+    for word in words {
+        hash ^= u64::from_le_bytes(word.try_into().unwrap());
+        hash = hash.wrapping_mul(PRIME).rotate_left(29);
+    }
+    for &byte in rest.iter().chain(&(bytes.len() as u64).to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    return hash;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::{NaiveDateTime, TimeDelta};
+    use regex::Regex;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::analyse::rules::{AlertRule, RuleTarget, Severity};
+    use crate::{Analysis, AnalysisConfig, TextSource};
+
+    fn log_lines(start: &str, count: i64) -> String {
+        let start = NaiveDateTime::parse_from_str(start, "%Y-%m-%d %H:%M:%S").unwrap();
+        let levels = ["INFO", "DEBUG", "WARN", "ERROR", "notice"];
+        let mut text = String::new();
+        for idx in 0..count {
+            let datetime = start + TimeDelta::seconds(idx * 7);
+            if idx % 97 == 13 {
+                text += &format!("{} garbled\n", datetime.format("%d/%m/%Y %H:%M:%S"));
+                continue;
+            }
+            if idx % 41 == 0 {
+                text += "  at continuation of the previous line\n";
+            }
+            text += &format!(
+                "{} [{}] worker-{} request {} {}\n",
+                datetime.format("%Y-%m-%d %H:%M:%S"),
+                levels[idx as usize % levels.len()],
+                idx % 4,
+                idx,
+                if idx % 11 == 0 { "timed out" } else { "done" }
+            );
+        }
+        return text;
+    }
+
+    fn config() -> AnalysisConfig {
+        let rule = AlertRule {
+            name: "timeouts".to_owned(),
+            target: RuleTarget::Pattern(Regex::new("timed out").unwrap()),
+            window: Some(TimeDelta::minutes(10)),
+            condition_text: ">5".to_owned(),
+            condition: ">5".parse().unwrap(),
+            severity: Severity::Warning,
+        };
+        return AnalysisConfig::builder("%Y-%m-%d %H:%M:%S")
+            .rules(vec![rule])
+            .build()
+            .unwrap();
+    }
+
+    // Everything an analysis found apart from when it was made
+    fn findings(analysis: &Analysis) -> Value {
+        let logs_analysis = &analysis.logs_analysis;
+        let logs_aggregate = &logs_analysis.logs_aggregate;
+        let mut aggregates: Vec<_> = logs_aggregate.aggregates.iter().collect();
+        aggregates.sort_unstable();
+        return json!({
+            "no_of_logs": analysis.no_of_logs,
+            "datetime_cat": logs_aggregate.datetime_cat,
+            "datetimes": logs_aggregate.datetimes,
+            "aggregates": aggregates,
+            "hour_weekday_counts": logs_analysis.hour_weekday_counts.iter().collect::<BTreeMap<_, _>>(),
+            "parse_failures": logs_analysis.parse_failures,
+            "metrics": logs_analysis.metrics,
+            "gaps": logs_analysis.gaps,
+            "rule_firings": logs_analysis.rule_firings,
+            "message_patterns": logs_analysis.message_patterns,
+            "time_span": logs_analysis.time_span,
+            "rejected_lines": logs_analysis.rejected_lines,
+        });
+    }
+
+    #[test]
+    fn resumed_analysis_equals_a_full_one() {
+        let config = config();
+        // The logs stop for a little over two hours, the shortest gap found
+        // with hourly buckets, and the last line is only partly written at
+        // the first run
+        let first_text = log_lines("2024-03-01 08:00:00", 600)
+            + &log_lines("2024-03-01 11:10:00", 900)
+            + "2024-03-01 13:00:00 [WARN] request";
+        let text =
+            first_text.clone() + " 1500 timed out\n" + &log_lines("2024-03-01 13:00:03", 900);
+        let first_source = TextSource {
+            name: "app.log".to_owned(),
+            text: first_text.clone(),
+        };
+        let source = TextSource {
+            name: "app.log".to_owned(),
+            text,
+        };
+
+        let (_, state) = config.analyse_incremental(&first_source, None).unwrap();
+        assert_eq!(state.files.len(), 1);
+        assert_eq!(
+            state.files[0].cache.offset,
+            first_text.rfind('\n').unwrap() + 1
+        );
+        // Through the file, as the state is read back by the next run
+        let state: AnalysisState =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+
+        let (resumed, state) = config.analyse_incremental(&source, Some(state)).unwrap();
+        let full = config.analyse(&source).unwrap();
+        assert!(!full.logs_analysis.rejected_lines.is_empty());
+        assert_eq!(full.logs_analysis.gaps.gaps.len(), 1);
+        assert!(!full.logs_analysis.rule_firings.as_ref().unwrap().is_empty());
+        assert_eq!(findings(&resumed), findings(&full));
+
+        // Nothing appended since
+        let (resumed, _) = config.analyse_incremental(&source, Some(state)).unwrap();
+        assert_eq!(findings(&resumed), findings(&full));
+    }
+
+    #[test]
+    fn resumed_analysis_keeps_the_gaps() {
+        let config = AnalysisConfig::builder("%Y-%m-%d %H:%M:%S")
+            .gap_threshold(TimeDelta::minutes(90))
+            .build()
+            .unwrap();
+        let first_text = [
+            "2024-03-01 08:00:00 INFO start",
+            "2024-03-01 09:00:00 INFO still going",
+            "2024-03-01 10:59:00 INFO back",
+            "2024-03-01 11:01:00 INFO going again",
+        ]
+        .join("\n")
+            + "\n";
+        let text = first_text.clone() + "2024-03-01 11:30:00 INFO appended\n";
+
+        let (_, state) = config
+            .analyse_incremental(
+                &TextSource {
+                    name: "app.log".to_owned(),
+                    text: first_text,
+                },
+                None,
+            )
+            .unwrap();
+        let source = TextSource {
+            name: "app.log".to_owned(),
+            text,
+        };
+        let (resumed, _) = config.analyse_incremental(&source, Some(state)).unwrap();
+        let full = config.analyse(&source).unwrap();
+        let gaps = &full.logs_analysis.gaps.gaps;
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].start.format("%H:%M").to_string(), "09:00");
+        assert_eq!(gaps[0].end.format("%H:%M").to_string(), "10:59");
+        assert_eq!(findings(&resumed), findings(&full));
+    }
+
+    #[test]
+    fn parses_again_a_changed_file() {
+        let config = config();
+        let text = log_lines("2024-03-01 08:00:00", 300);
+        let (_, state) = config
+            .analyse_incremental(
+                &TextSource {
+                    name: "app.log".to_owned(),
+                    text: text.clone(),
+                },
+                None,
+            )
+            .unwrap();
+
+        // Same length, but changed before where it was parsed
+        let source = TextSource {
+            name: "app.log".to_owned(),
+            text: text.replacen("[INFO]", "[CRIT]", 1),
+        };
+        let (resumed, _) = config.analyse_incremental(&source, Some(state)).unwrap();
+        let full = config.analyse(&source).unwrap();
+        assert_eq!(findings(&resumed), findings(&full));
+    }
+
+    #[test]
+    fn checksum_covers_every_byte() {
+        let bytes: Vec<u8> = (0..10_000u32).map(|idx| (idx % 251) as u8).collect();
+        let hash = checksum(&bytes);
+        for idx in [0, 7, 8, 5000, 9_999] {
+            let mut changed = bytes.clone();
+            changed[idx] ^= 1;
+            assert_ne!(checksum(&changed), hash, "{}", idx);
+        }
+        assert_ne!(checksum(&bytes[..9_999]), hash);
+        assert_eq!(checksum(&bytes), hash);
+    }
+}
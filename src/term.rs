@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::io::{stdout, IsTerminal};
+
+use plotters::style::RGBColor;
+
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const BAR_EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// One block per value scaled to the largest value, empty values are left
+/// blank so that silent periods stand out.
+pub fn sparkline(values: &[i32]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+
+    return values
+        .iter()
+        .map(|value| {
+            if *value <= 0 {
+                return ' ';
+            }
+            let level = (*value as f64 / max as f64 * SPARK_BLOCKS.len() as f64).ceil() as usize;
+            SPARK_BLOCKS[level.clamp(1, SPARK_BLOCKS.len()) - 1]
+        })
+        .collect();
+}
+
+pub fn colorize(text: &str, color: &RGBColor, enabled: bool) -> String {
+    if !enabled {
+        return text.to_owned();
+    }
+    return format!(
+        "\x1b[38;2;{};{};{}m{}\x1b[0m",
+        color.0, color.1, color.2, text
+    );
+}
+
+pub fn use_colors() -> bool {
+    return stdout().is_terminal();
+}
+
+/// Horizontal bar of `value` relative to `max`, `width` characters long when
+/// the value is the max, drawn to an eighth of a character.
+pub fn bar(value: i32, max: i32, width: usize) -> String {
+    if value <= 0 || max <= 0 {
+        return String::new();
+    }
+    let eighths = (value as f64 / max as f64 * (width * 8) as f64).round() as usize;
+    let eighths = eighths.max(1);
+    let mut text = "█".repeat(eighths / 8);
+    if !eighths.is_multiple_of(8) {
+        text.push(BAR_EIGHTHS[eighths % 8]);
+    }
+
+    return text;
+}
+
+/// Sums neighbouring values so that there are at most `max_len` of them.
+pub fn downsample(values: &[i32], max_len: usize) -> Vec<i32> {
+    if values.len() <= max_len || max_len == 0 {
+        return values.to_vec();
+    }
+    let chunk = values.len().div_ceil(max_len);
+
+    return values
+        .chunks(chunk)
+        .map(|chunk| chunk.iter().sum())
+        .collect();
+}